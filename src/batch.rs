@@ -0,0 +1,240 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use crate::{key::Key, model::KbtError, report::Report};
+
+// Production line run: each unit is identified by its serial, typed or scanned with a barcode
+// reader that types it, and passes once every key was pressed without problems
+#[derive(Default)]
+pub struct Batch {
+    // Serial being typed, the test waits while this is Some
+    pub serial_input: Option<String>,
+    pub passed: u32,
+    pub failed: u32,
+    pub last_unit: Option<UnitResult>,
+    // Scanners type upper case letters with Shift held
+    held_shifts: HashSet<Key>,
+}
+
+pub struct UnitResult {
+    pub serial: String,
+    pub problems: Vec<String>,
+}
+
+impl Batch {
+    pub fn new() -> Batch {
+        Batch {
+            serial_input: Some(String::new()),
+            ..Batch::default()
+        }
+    }
+
+    // The finished serial once Return is pressed
+    pub fn type_key(&mut self, key: Key) -> Option<String> {
+        if matches!(key, Key::LeftShift | Key::RightShift) {
+            self.held_shifts.insert(key);
+        }
+        let shift = !self.held_shifts.is_empty();
+        let input = self.serial_input.as_mut()?;
+        match key {
+            Key::Return | Key::NumpadEnter if !input.is_empty() => self.serial_input.take(),
+            Key::Backspace => {
+                input.pop();
+                None
+            }
+            _ => {
+                match serial_char(key, shift) {
+                    // A double dot isn't a serial
+                    Some('.') if input.ends_with('.') => {}
+                    Some(char) => input.push(char),
+                    None => {}
+                }
+                None
+            }
+        }
+    }
+
+    pub fn release_key(&mut self, key: Key) {
+        self.held_shifts.remove(&key);
+    }
+
+    pub fn record(&mut self, serial: String, report: &Report) {
+        let problems = problems(report);
+        if problems.is_empty() {
+            self.passed += 1;
+        } else {
+            self.failed += 1;
+        }
+
+        self.last_unit = Some(UnitResult { serial, problems });
+        self.serial_input = Some(String::new());
+        // Shift releases during the test went to the board
+        self.held_shifts.clear();
+    }
+
+    pub fn tally(&self) -> String {
+        format!("{} passed, {} failed", self.passed, self.failed)
+    }
+}
+
+fn serial_char(key: Key, shift: bool) -> Option<char> {
+    let char = match key {
+        Key::A => 'a',
+        Key::B => 'b',
+        Key::C => 'c',
+        Key::D => 'd',
+        Key::E => 'e',
+        Key::F => 'f',
+        Key::G => 'g',
+        Key::H => 'h',
+        Key::I => 'i',
+        Key::J => 'j',
+        Key::K => 'k',
+        Key::L => 'l',
+        Key::M => 'm',
+        Key::N => 'n',
+        Key::O => 'o',
+        Key::P => 'p',
+        Key::Q => 'q',
+        Key::R => 'r',
+        Key::S => 's',
+        Key::T => 't',
+        Key::U => 'u',
+        Key::V => 'v',
+        Key::W => 'w',
+        Key::X => 'x',
+        Key::Y => 'y',
+        Key::Z => 'z',
+        _ if shift => return None,
+        Key::One | Key::NumpadOne => '1',
+        Key::Two | Key::NumpadTwo => '2',
+        Key::Three | Key::NumpadThree => '3',
+        Key::Four | Key::NumpadFour => '4',
+        Key::Five | Key::NumpadFive => '5',
+        Key::Six | Key::NumpadSix => '6',
+        Key::Seven | Key::NumpadSeven => '7',
+        Key::Eight | Key::NumpadEight => '8',
+        Key::Nine | Key::NumpadNine => '9',
+        Key::Zero | Key::NumpadZero => '0',
+        Key::Hyphen | Key::Minus => '-',
+        Key::Period | Key::Decimal => '.',
+        _ => return None,
+    };
+
+    Some(if shift {
+        char.to_ascii_uppercase()
+    } else {
+        char
+    })
+}
+
+// Serials end up in report file names, only what a serial can be typed with is allowed
+pub fn check_serial(serial: &str) -> Result<(), KbtError> {
+    let typeable = serial
+        .chars()
+        .all(|char| char.is_ascii_alphanumeric() || matches!(char, '-' | '.'));
+    if serial.is_empty() || !typeable || serial.contains("..") {
+        return Err(KbtError {
+            message: format!(
+                "{} is not a serial, use letters, digits, - and single dots",
+                serial
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+fn problems(report: &Report) -> Vec<String> {
+    let mut problems: Vec<String> = Vec::new();
+    if !report.untested_keys.is_empty() {
+        problems.push(format!("{} untested keys", report.untested_keys.len()));
+    }
+
+    let failures = [
+        ("stuck", &report.stuck_keys),
+        ("lost release", &report.lost_releases),
+        ("chatter", &report.chatter_keys),
+        ("ghost", &report.ghost_keys),
+    ];
+    for (failure, keys) in failures {
+        if !keys.is_empty() {
+            let names: Vec<String> = keys.iter().map(|key| format!("{:?}", key)).collect();
+            problems.push(format!("{} {}", failure, names.join(" ")));
+        }
+    }
+
+    problems
+}
+
+// Every unit gets its own report files, report.json becomes report-SN123.json next to it
+pub fn unit_path(path: &Path, serial: &str) -> Result<PathBuf, KbtError> {
+    check_serial(serial)?;
+
+    let stem = path
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().to_string());
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, serial, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, serial),
+    };
+
+    Ok(path.with_file_name(file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn types_a_scanned_serial() {
+        let mut batch = Batch::new();
+        let scanned = [
+            (Key::LeftShift, true),
+            (Key::S, true),
+            (Key::N, true),
+            (Key::LeftShift, false),
+            (Key::Hyphen, true),
+            (Key::One, true),
+            (Key::Period, true),
+            (Key::Two, true),
+            (Key::A, true),
+        ];
+        for (key, pressed) in scanned {
+            if pressed {
+                assert_eq!(batch.type_key(key), None);
+            } else {
+                batch.release_key(key);
+            }
+        }
+
+        assert_eq!(batch.type_key(Key::Return), Some(String::from("SN-1.2a")));
+    }
+
+    #[test]
+    fn ignores_shifted_symbols() {
+        let mut batch = Batch::new();
+        for key in [Key::RightShift, Key::One, Key::Hyphen, Key::Decimal] {
+            batch.type_key(key);
+        }
+        batch.release_key(Key::RightShift);
+        batch.type_key(Key::NumpadOne);
+
+        assert_eq!(batch.type_key(Key::NumpadEnter), Some(String::from("1")));
+    }
+
+    #[test]
+    fn unit_reports_stay_next_to_the_report() {
+        let path = Path::new("reports/report.json");
+        assert_eq!(
+            unit_path(path, "SN-1.2a").ok(),
+            Some(PathBuf::from("reports/report-SN-1.2a.json"))
+        );
+
+        for serial in ["../x", "a/b", "/tmp/x", "..", "a\\b", ""] {
+            assert!(unit_path(path, serial).is_err(), "{} was accepted", serial);
+        }
+    }
+}
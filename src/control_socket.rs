@@ -0,0 +1,172 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+    thread,
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    batch, event_stream, load_layout,
+    model::{AppEvent, ControlEventType, ExtraRow, KbtError, KeyboardSize, TestMode},
+};
+
+const COMMANDS: &str = "reset, select-layout <60|80|100|apple-ansi|apple-iso|jis|abnt2|korean>, start-test [serial], finish, export-report <path>, quit";
+
+// One command per line, each answered with `ok` or `error: ...` once it was handed to the session
+pub struct ControlSocket {
+    path: PathBuf,
+}
+
+// What the session accepts, commands that don't apply are answered with an error right away
+#[derive(Clone)]
+pub struct SessionKind {
+    pub extra_rows: Vec<ExtraRow>,
+    pub batch: bool,
+    pub mode: TestMode,
+}
+
+impl ControlSocket {
+    pub fn serve(
+        path: &Path,
+        sender: Sender<AppEvent>,
+        session: SessionKind,
+    ) -> Result<ControlSocket, KbtError> {
+        let listener = event_stream::bind(path)?;
+        thread::spawn(move || {
+            for client in listener.incoming().flatten() {
+                let sender = sender.clone();
+                let session = session.clone();
+                thread::spawn(move || handle_client(client, &sender, &session));
+            }
+        });
+
+        Ok(ControlSocket {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn handle_client(client: UnixStream, sender: &Sender<AppEvent>, session: &SessionKind) {
+    let Ok(mut writer) = client.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(client).lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match parse(&line, session) {
+            Ok(control) => match sender.send(AppEvent::ControlEvent(control)) {
+                Ok(_) => String::from("ok"),
+                // The session is over
+                Err(_) => return,
+            },
+            Err(KbtError { message }) => format!("error: {}", message),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+fn parse(line: &str, session: &SessionKind) -> Result<ControlEventType, KbtError> {
+    let (command, argument) = match line.trim().split_once(char::is_whitespace) {
+        Some((command, argument)) => (command, Some(argument.trim())),
+        None => (line.trim(), None),
+    };
+
+    match (command, argument) {
+        ("reset", None) => Ok(ControlEventType::Reset),
+        ("quit", None) => Ok(ControlEventType::Terminate),
+        ("finish", None) if !session.batch => Err(KbtError {
+            message: String::from("finish only applies in batch mode"),
+        }),
+        ("finish", None) => Ok(ControlEventType::Finish),
+        ("start-test", Some(serial)) => batch::check_serial(serial)
+            .map(|_| ControlEventType::StartTest(Some(String::from(serial)))),
+        ("start-test", None) => Ok(ControlEventType::StartTest(None)),
+        ("select-layout", Some(_)) if matches!(session.mode, TestMode::Matrix) => Err(KbtError {
+            message: String::from(
+                "built-in layouts have no switch matrix, select-layout doesn't apply in matrix mode",
+            ),
+        }),
+        ("select-layout", Some(name)) => KeyboardSize::from_str(name, true)
+            .map(|selection| {
+                ControlEventType::SelectLayout(Box::new(load_layout(
+                    &selection,
+                    &session.extra_rows,
+                )))
+            })
+            .map_err(|_| KbtError {
+                message: format!("unknown layout {}", name),
+            }),
+        ("export-report", Some(path)) => Ok(ControlEventType::ExportReport(PathBuf::from(path))),
+        _ => Err(KbtError {
+            message: format!(
+                "unknown command {}, expected one of: {}",
+                line.trim(),
+                COMMANDS
+            ),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(batch: bool, mode: TestMode) -> SessionKind {
+        SessionKind {
+            extra_rows: Vec::new(),
+            batch,
+            mode,
+        }
+    }
+
+    fn error(line: &str, session: &SessionKind) -> Option<String> {
+        parse(line, session).err().map(|err| err.message)
+    }
+
+    #[test]
+    fn finish_needs_batch_mode() {
+        assert_eq!(
+            error("finish", &session(false, TestMode::Keys)).as_deref(),
+            Some("finish only applies in batch mode")
+        );
+        assert!(matches!(
+            parse("finish", &session(true, TestMode::Keys)),
+            Ok(ControlEventType::Finish)
+        ));
+    }
+
+    #[test]
+    fn start_test_takes_typeable_serials_only() {
+        let session = session(true, TestMode::Keys);
+        assert!(matches!(
+            parse("start-test SN-1.2a", &session),
+            Ok(ControlEventType::StartTest(Some(serial))) if serial == "SN-1.2a"
+        ));
+        for serial in ["../../tmp/x", "a/b", "..", "SN 1"] {
+            assert!(error(&format!("start-test {}", serial), &session).is_some());
+        }
+    }
+
+    #[test]
+    fn select_layout_is_refused_in_matrix_mode() {
+        assert!(error("select-layout 60", &session(false, TestMode::Matrix)).is_some());
+        assert!(matches!(
+            parse("select-layout 60", &session(false, TestMode::Keys)),
+            Ok(ControlEventType::SelectLayout(_))
+        ));
+    }
+}
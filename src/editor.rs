@@ -0,0 +1,334 @@
+use std::path::PathBuf;
+
+use crate::{
+    key::Key,
+    layout_file::{LayoutFile, LayoutFileKey},
+    model::{KbtError, KeyEvent, KeySize, KeyboardLayout, VerticalKeyPart},
+    prepare_layout,
+};
+
+// Layout file being edited. Keys are driven by the key backend, so the board doesn't need to be
+// the one the terminal is focused on, and a slot gets its key by pressing it.
+pub struct LayoutEditor {
+    pub layout_file: LayoutFile,
+    // Last saved or loaded state, ctrl+r goes back to it
+    saved: LayoutFile,
+    pub path: PathBuf,
+    pub row: usize,
+    pub col: usize,
+    // The next key press is assigned to the slot under the cursor
+    pub assigning: bool,
+    pub message: Option<String>,
+}
+
+impl LayoutEditor {
+    pub fn new(layout_file: LayoutFile, path: PathBuf) -> LayoutEditor {
+        LayoutEditor {
+            saved: layout_file.clone(),
+            layout_file,
+            path,
+            row: 0,
+            col: 0,
+            assigning: false,
+            message: None,
+        }
+    }
+
+    pub fn layout(&self) -> KeyboardLayout {
+        prepare_layout(
+            self.layout_file.name.clone(),
+            self.layout_file.key_rows(),
+            self.layout_file.legends,
+            self.layout_file.matrix(),
+        )
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        serde_json::to_value(&self.layout_file).ok() != serde_json::to_value(&self.saved).ok()
+    }
+
+    pub fn current(&self) -> Option<&LayoutFileKey> {
+        self.layout_file
+            .rows
+            .get(self.row)
+            .and_then(|row| row.get(self.col))
+    }
+
+    fn current_mut(&mut self) -> Option<&mut LayoutFileKey> {
+        self.layout_file
+            .rows
+            .get_mut(self.row)
+            .and_then(|row| row.get_mut(self.col))
+    }
+
+    // Tall keys are a Top entry and a Bottom entry with the same key in the next row, edits
+    // to one half go to both
+    fn other_half(&self) -> Option<(usize, usize)> {
+        let current = self.current()?;
+        let row = match current.part {
+            Some(VerticalKeyPart::Top) => self.row + 1,
+            Some(VerticalKeyPart::Bottom) => self.row.checked_sub(1)?,
+            _ => return None,
+        };
+        let part = match current.part {
+            Some(VerticalKeyPart::Top) => VerticalKeyPart::Bottom,
+            _ => VerticalKeyPart::Top,
+        };
+        let col = self
+            .layout_file
+            .rows
+            .get(row)?
+            .iter()
+            .position(|layout_key| {
+                layout_key.key == current.key && layout_key.part == Some(part)
+            })?;
+        Some((row, col))
+    }
+
+    // The key under the cursor and its other half, if it has one
+    fn edit_current(&mut self, edit: impl Fn(&mut LayoutFileKey)) {
+        let other_half = self.other_half();
+        if let Some(current) = self.current_mut() {
+            edit(current);
+        }
+        if let Some((row, col)) = other_half {
+            edit(&mut self.layout_file.rows[row][col]);
+        }
+    }
+
+    pub fn press(&mut self, key_event: &KeyEvent) {
+        self.message = None;
+
+        if self.assigning {
+            self.assigning = false;
+            match key_event.key {
+                Some(key) => self.assign(key),
+                None => {
+                    self.message =
+                        Some(format!("kbt has no key for {}", key_event.raw_code_label()));
+                }
+            }
+            return;
+        }
+
+        let Some(key) = key_event.key else {
+            return;
+        };
+        match key {
+            Key::ArrowUp => self.move_to_row(self.row.saturating_sub(1)),
+            Key::ArrowDown => self.move_to_row(self.row + 1),
+            Key::ArrowLeft => self.col = self.col.saturating_sub(1),
+            Key::ArrowRight => self.move_to_col(self.col + 1),
+            Key::Return => self.assigning = true,
+            Key::I => {
+                self.insert(Key::Separator);
+                self.assigning = true;
+            }
+            Key::G => self.insert(Key::Separator),
+            Key::N => self.insert_row(),
+            Key::Delete | Key::Backspace => self.remove(),
+            Key::Equal | Key::Plus => self.resize(1),
+            Key::Hyphen | Key::Minus => self.resize(-1),
+            Key::RightBracket => self.correct(1),
+            Key::LeftBracket => self.correct(-1),
+            Key::S => self.save(),
+            _ => {}
+        }
+    }
+
+    pub fn revert(&mut self) {
+        self.layout_file = self.saved.clone();
+        self.assigning = false;
+        self.move_to_row(self.row);
+        self.message = Some(String::from("reverted to the last saved layout"));
+    }
+
+    fn move_to_row(&mut self, row: usize) {
+        self.row = row.min(self.layout_file.rows.len().saturating_sub(1));
+        self.move_to_col(self.col);
+    }
+
+    fn move_to_col(&mut self, col: usize) {
+        let row_len = self.layout_file.rows.get(self.row).map_or(0, Vec::len);
+        self.col = col.min(row_len.saturating_sub(1));
+    }
+
+    fn assign(&mut self, key: Key) {
+        let (row, col) = (self.row, self.col);
+        let other_half = self.other_half();
+        let duplicate = self
+            .layout_file
+            .rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row_idx, keys)| {
+                keys.iter()
+                    .enumerate()
+                    .map(move |(col_idx, layout_key)| (row_idx, col_idx, layout_key))
+            })
+            .find(|(row_idx, col_idx, layout_key)| {
+                layout_key.key == key
+                    && (*row_idx, *col_idx) != (row, col)
+                    && Some((*row_idx, *col_idx)) != other_half
+            })
+            .map(|(row_idx, _, _)| row_idx);
+
+        self.edit_current(|current| current.key = key);
+        if let Some(row_idx) = duplicate {
+            self.message = Some(format!("{:?} is also on row {}", key, row_idx + 1));
+        }
+    }
+
+    // New slots go after the cursor, empty rows get one
+    fn insert(&mut self, key: Key) {
+        let new_key = LayoutFileKey {
+            key,
+            size: KeySize::U1,
+            correction: None,
+            part: None,
+            matrix: None,
+        };
+
+        match self.layout_file.rows.get_mut(self.row) {
+            Some(row) if !row.is_empty() => {
+                row.insert(self.col + 1, new_key);
+                self.col += 1;
+            }
+            Some(row) => row.push(new_key),
+            None => self.layout_file.rows.push(vec![new_key]),
+        }
+    }
+
+    fn insert_row(&mut self) {
+        let row = (self.row + 1).min(self.layout_file.rows.len());
+        self.layout_file.rows.insert(row, Vec::new());
+        self.row = row;
+        self.col = 0;
+        self.insert(Key::Separator);
+    }
+
+    fn remove(&mut self) {
+        let mut slots: Vec<(usize, usize)> = self.other_half().into_iter().collect();
+        slots.push((self.row, self.col));
+        let keys_count: usize = self.layout_file.rows.iter().map(Vec::len).sum();
+        if keys_count <= slots.len() {
+            self.message = Some(String::from("a layout needs at least one key"));
+            return;
+        }
+
+        // The lower row goes first so the index of the upper one still holds
+        slots.sort_unstable_by(|a, b| b.cmp(a));
+        for (row_idx, col) in slots {
+            if let Some(row) = self.layout_file.rows.get_mut(row_idx) {
+                if col < row.len() {
+                    row.remove(col);
+                }
+                if row.is_empty() {
+                    self.layout_file.rows.remove(row_idx);
+                    if row_idx < self.row {
+                        self.row -= 1;
+                    }
+                }
+            }
+        }
+        self.move_to_row(self.row);
+    }
+
+    // Steps through the standard key sizes, dropping any correction
+    fn resize(&mut self, step: isize) {
+        let Some(current) = self.current() else {
+            return;
+        };
+        let idx = KeySize::ALL
+            .iter()
+            .position(|size| size.static_len() == current.size.static_len())
+            .unwrap_or(0);
+        let idx = idx.saturating_add_signed(step).min(KeySize::ALL.len() - 1);
+        self.edit_current(|current| {
+            current.size = KeySize::ALL[idx];
+            current.correction = None;
+        });
+    }
+
+    // Fine-tunes the width one terminal cell (0.2u) at a time
+    fn correct(&mut self, cells: i16) {
+        let Some(current) = self.current() else {
+            return;
+        };
+        let correction = current.correction.unwrap_or(0) + cells;
+        if current.size.static_len() as i16 + correction > 0 {
+            self.edit_current(|current| {
+                current.correction = (correction != 0).then_some(correction)
+            });
+        }
+    }
+
+    fn save(&mut self) {
+        self.message = Some(match self.layout_file.save(&self.path) {
+            Ok(_) => {
+                self.saved = self.layout_file.clone();
+                format!("saved to {}", self.path.display())
+            }
+            Err(KbtError { message }) => message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Legends;
+
+    fn layout_key(key: Key, part: Option<VerticalKeyPart>) -> LayoutFileKey {
+        LayoutFileKey {
+            key,
+            size: KeySize::U1,
+            correction: None,
+            part,
+            matrix: None,
+        }
+    }
+
+    // A numpad corner, Plus spans both rows
+    fn editor() -> LayoutEditor {
+        let rows = vec![
+            vec![
+                layout_key(Key::NumpadNine, None),
+                layout_key(Key::Plus, Some(VerticalKeyPart::Top)),
+            ],
+            vec![
+                layout_key(Key::NumpadSix, None),
+                layout_key(Key::Plus, Some(VerticalKeyPart::Bottom)),
+            ],
+        ];
+        let layout_file = LayoutFile {
+            name: String::from("test"),
+            legends: Legends::default(),
+            rows,
+            unbound_keys: 0,
+        };
+        let mut editor = LayoutEditor::new(layout_file, PathBuf::from("test.json"));
+        editor.row = 1;
+        editor.col = 1;
+        editor
+    }
+
+    #[test]
+    fn edits_both_halves_of_a_tall_key() {
+        let mut editor = editor();
+        editor.assign(Key::NumpadEnter);
+        assert!(editor.message.is_none());
+        assert_eq!(editor.layout_file.rows[0][1].key, Key::NumpadEnter);
+        assert_eq!(editor.layout_file.rows[1][1].key, Key::NumpadEnter);
+
+        editor.resize(1);
+        editor.correct(-1);
+        for row in &editor.layout_file.rows {
+            assert_eq!(row[1].size.static_len(), KeySize::U125.static_len());
+            assert_eq!(row[1].correction, Some(-1));
+        }
+
+        editor.remove();
+        assert!(editor.layout_file.rows.iter().all(|row| row.len() == 1));
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::mpsc::Sender,
+    thread,
+    time::{Instant, SystemTime},
+};
+
+use evdev::{Device, InputEvent, InputEventKind, LedType};
+
+use crate::{
+    key::Key,
+    leds::LockLeds,
+    model::{AppEvent, InputDevice, KbtError, KeyEvent, KeyEventType, RawKeyCode},
+};
+
+pub struct EvdevKeyBackend;
+
+impl EvdevKeyBackend {
+    // Nodes of one physical keyboard are grouped, the keyboard node names the group
+    pub fn list_keyboards() -> Vec<InputDevice> {
+        let mut nodes: Vec<(PathBuf, Device)> = evdev::enumerate()
+            .filter(|(_, device)| is_key_source(device))
+            .collect();
+        nodes.sort_by_key(|(path, device)| (!is_keyboard(device), path.clone()));
+
+        let mut keyboards: Vec<(Option<String>, InputDevice)> = Vec::new();
+        for (path, device) in nodes {
+            let group = physical_device(&device);
+            match keyboards
+                .iter_mut()
+                .find(|(other, _)| group.is_some() && *other == group)
+            {
+                Some((_, keyboard)) => keyboard.nodes.push(path),
+                None => keyboards.push((
+                    group,
+                    InputDevice {
+                        name: device.name().unwrap_or("unknown").to_string(),
+                        path: path.clone(),
+                        nodes: vec![path],
+                    },
+                )),
+            }
+        }
+        let mut keyboards: Vec<InputDevice> = keyboards
+            .into_iter()
+            .map(|(_, keyboard)| keyboard)
+            .collect();
+        keyboards.sort_by(|a, b| a.path.cmp(&b.path));
+
+        keyboards
+    }
+
+    // Keys the kernel currently holds down, on one device or on all keyboards
+    pub fn pressed_keys(device: Option<&InputDevice>) -> Result<HashSet<Key>, KbtError> {
+        let mut pressed_keys: HashSet<Key> = HashSet::new();
+        for (path, keyboard) in evdev::enumerate().filter(|(_, device)| is_key_source(device)) {
+            if device.is_some_and(|device| !device.owns(&path)) {
+                continue;
+            }
+            pressed_keys.extend(keyboard.get_key_state()?.iter().filter_map(map_key));
+        }
+
+        Ok(pressed_keys)
+    }
+
+    pub fn subscribe(sender: &Sender<AppEvent>) -> Result<(), KbtError> {
+        let keyboards: Vec<(PathBuf, Device)> = evdev::enumerate()
+            .filter(|(_, device)| is_key_source(device))
+            .collect();
+
+        if keyboards.is_empty() {
+            return Err(KbtError {
+                message: String::from(
+                    "No readable keyboards in /dev/input, check that you are in the input group",
+                ),
+            });
+        }
+
+        for (path, mut device) in keyboards {
+            let sender = sender.clone();
+            let name = device.name().unwrap_or("unknown").to_string();
+            let clock_anchor = (Instant::now(), SystemTime::now());
+            thread::spawn(move || loop {
+                let events = match device.fetch_events() {
+                    Ok(events) => events,
+                    Err(err) => {
+                        log::error!("Failed to read events from {}: {}", name, err);
+                        return;
+                    }
+                };
+
+                for event in events {
+                    if let InputEventKind::Key(code) = event.kind() {
+                        let key_event = KeyEvent {
+                            key: map_key(code),
+                            raw_code: Some(RawKeyCode::Evdev {
+                                code: code.code(),
+                                name: format!("{:?}", code),
+                            }),
+                            device: Some(path.clone()),
+                            timestamp: kernel_instant(&event, clock_anchor),
+                        };
+                        let key_event = match event.value() {
+                            1 => KeyEventType::KeyPressed(key_event),
+                            0 => KeyEventType::KeyReleased(key_event),
+                            _ => continue,
+                        };
+
+                        if let Err(err) = sender.send(AppEvent::KeyEvent(key_event)) {
+                            log::error!("Evdev channel died {}", err);
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+// LEDs are shared by all keyboards in practice, any lit one counts
+pub struct EvdevLeds {
+    keyboards: Vec<Device>,
+}
+
+impl EvdevLeds {
+    pub fn open(device: Option<&InputDevice>) -> EvdevLeds {
+        EvdevLeds {
+            keyboards: evdev::enumerate()
+                .filter(|(path, keyboard)| {
+                    is_keyboard(keyboard) && device.is_none_or(|device| device.owns(path))
+                })
+                .map(|(_, keyboard)| keyboard)
+                .collect(),
+        }
+    }
+
+    pub fn read(&self) -> Result<LockLeds, KbtError> {
+        let mut leds = LockLeds::default();
+        for keyboard in &self.keyboards {
+            let led_state = keyboard.get_led_state()?;
+            leds.caps_lock |= led_state.contains(LedType::LED_CAPSL);
+            leds.num_lock |= led_state.contains(LedType::LED_NUML);
+            leds.scroll_lock |= led_state.contains(LedType::LED_SCROLLL);
+        }
+
+        Ok(leds)
+    }
+}
+
+// The kernel stamps events with the wall clock, move that onto the monotonic clock kbt measures with.
+// A fixed anchor per device keeps the kernel spacing between events exact, events of one report stay equal.
+fn kernel_instant(event: &InputEvent, (anchor, anchor_time): (Instant, SystemTime)) -> Instant {
+    match event.timestamp().duration_since(anchor_time) {
+        Ok(since_anchor) => anchor + since_anchor,
+        Err(err) => anchor.checked_sub(err.duration()).unwrap_or(anchor),
+    }
+}
+
+fn is_keyboard(device: &Device) -> bool {
+    device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(evdev::Key::KEY_ENTER))
+}
+
+// Keyboards and the Consumer Control nodes that carry their media keys
+fn is_key_source(device: &Device) -> bool {
+    is_keyboard(device)
+        || device.supported_keys().is_some_and(|keys| {
+            [
+                evdev::Key::KEY_MUTE,
+                evdev::Key::KEY_VOLUMEUP,
+                evdev::Key::KEY_PLAYPAUSE,
+            ]
+            .iter()
+            .any(|key| keys.contains(*key))
+        })
+}
+
+// Interfaces of one USB or Bluetooth keyboard share the ids and the phys path up to `/inputN`
+fn physical_device(device: &Device) -> Option<String> {
+    let phys = device.physical_path().filter(|phys| !phys.is_empty())?;
+    let port = phys.rsplit_once('/').map_or(phys, |(port, _)| port);
+    let id = device.input_id();
+
+    Some(format!(
+        "{:04x}:{:04x}:{:04x} {}",
+        id.bus_type().0,
+        id.vendor(),
+        id.product(),
+        port
+    ))
+}
+
+fn map_key(code: evdev::Key) -> Option<Key> {
+    let key = match code {
+        evdev::Key::KEY_0 => Key::Zero,
+        evdev::Key::KEY_1 => Key::One,
+        evdev::Key::KEY_2 => Key::Two,
+        evdev::Key::KEY_3 => Key::Three,
+        evdev::Key::KEY_4 => Key::Four,
+        evdev::Key::KEY_5 => Key::Five,
+        evdev::Key::KEY_6 => Key::Six,
+        evdev::Key::KEY_7 => Key::Seven,
+        evdev::Key::KEY_8 => Key::Eight,
+        evdev::Key::KEY_9 => Key::Nine,
+        evdev::Key::KEY_A => Key::A,
+        evdev::Key::KEY_B => Key::B,
+        evdev::Key::KEY_C => Key::C,
+        evdev::Key::KEY_D => Key::D,
+        evdev::Key::KEY_E => Key::E,
+        evdev::Key::KEY_F => Key::F,
+        evdev::Key::KEY_G => Key::G,
+        evdev::Key::KEY_H => Key::H,
+        evdev::Key::KEY_I => Key::I,
+        evdev::Key::KEY_J => Key::J,
+        evdev::Key::KEY_K => Key::K,
+        evdev::Key::KEY_L => Key::L,
+        evdev::Key::KEY_M => Key::M,
+        evdev::Key::KEY_N => Key::N,
+        evdev::Key::KEY_O => Key::O,
+        evdev::Key::KEY_P => Key::P,
+        evdev::Key::KEY_Q => Key::Q,
+        evdev::Key::KEY_R => Key::R,
+        evdev::Key::KEY_S => Key::S,
+        evdev::Key::KEY_T => Key::T,
+        evdev::Key::KEY_U => Key::U,
+        evdev::Key::KEY_V => Key::V,
+        evdev::Key::KEY_W => Key::W,
+        evdev::Key::KEY_X => Key::X,
+        evdev::Key::KEY_Y => Key::Y,
+        evdev::Key::KEY_Z => Key::Z,
+        evdev::Key::KEY_F1 => Key::F1,
+        evdev::Key::KEY_F2 => Key::F2,
+        evdev::Key::KEY_F3 => Key::F3,
+        evdev::Key::KEY_F4 => Key::F4,
+        evdev::Key::KEY_F5 => Key::F5,
+        evdev::Key::KEY_F6 => Key::F6,
+        evdev::Key::KEY_F7 => Key::F7,
+        evdev::Key::KEY_F8 => Key::F8,
+        evdev::Key::KEY_F9 => Key::F9,
+        evdev::Key::KEY_F10 => Key::F10,
+        evdev::Key::KEY_F11 => Key::F11,
+        evdev::Key::KEY_F12 => Key::F12,
+        evdev::Key::KEY_F13 => Key::F13,
+        evdev::Key::KEY_F14 => Key::F14,
+        evdev::Key::KEY_F15 => Key::F15,
+        evdev::Key::KEY_F16 => Key::F16,
+        evdev::Key::KEY_F17 => Key::F17,
+        evdev::Key::KEY_F18 => Key::F18,
+        evdev::Key::KEY_F19 => Key::F19,
+        evdev::Key::KEY_F20 => Key::F20,
+        evdev::Key::KEY_F21 => Key::F21,
+        evdev::Key::KEY_F22 => Key::F22,
+        evdev::Key::KEY_F23 => Key::F23,
+        evdev::Key::KEY_F24 => Key::F24,
+        evdev::Key::KEY_ESC => Key::Esc,
+        evdev::Key::KEY_SPACE => Key::Spacebar,
+        evdev::Key::KEY_LEFTCTRL => Key::LeftCtrl,
+        evdev::Key::KEY_RIGHTCTRL => Key::RightCtrl,
+        evdev::Key::KEY_LEFTSHIFT => Key::LeftShift,
+        evdev::Key::KEY_RIGHTSHIFT => Key::RightShift,
+        evdev::Key::KEY_LEFTALT => Key::LeftAlt,
+        evdev::Key::KEY_RIGHTALT => Key::RightAlt,
+        evdev::Key::KEY_LEFTMETA => Key::LeftSuper,
+        evdev::Key::KEY_RIGHTMETA => Key::RightSuper,
+        evdev::Key::KEY_FN => Key::Fn,
+        evdev::Key::KEY_ENTER => Key::Return,
+        evdev::Key::KEY_UP => Key::ArrowUp,
+        evdev::Key::KEY_DOWN => Key::ArrowDown,
+        evdev::Key::KEY_LEFT => Key::ArrowLeft,
+        evdev::Key::KEY_RIGHT => Key::ArrowRight,
+        evdev::Key::KEY_BACKSPACE => Key::Backspace,
+        evdev::Key::KEY_CAPSLOCK => Key::CapsLock,
+        evdev::Key::KEY_TAB => Key::Tab,
+        evdev::Key::KEY_HOME => Key::Home,
+        evdev::Key::KEY_END => Key::End,
+        evdev::Key::KEY_PAGEUP => Key::PgUp,
+        evdev::Key::KEY_PAGEDOWN => Key::PgDown,
+        evdev::Key::KEY_INSERT => Key::Insert,
+        evdev::Key::KEY_DELETE => Key::Delete,
+        evdev::Key::KEY_GRAVE => Key::Grave,
+        evdev::Key::KEY_MINUS => Key::Hyphen,
+        evdev::Key::KEY_EQUAL => Key::Equal,
+        evdev::Key::KEY_LEFTBRACE => Key::LeftBracket,
+        evdev::Key::KEY_RIGHTBRACE => Key::RightBracket,
+        evdev::Key::KEY_BACKSLASH => Key::Backslash,
+        evdev::Key::KEY_SEMICOLON => Key::SemiColon,
+        evdev::Key::KEY_APOSTROPHE => Key::Apostrophe,
+        evdev::Key::KEY_COMMA => Key::Comma,
+        evdev::Key::KEY_DOT => Key::Period,
+        evdev::Key::KEY_SLASH => Key::QuestionMark,
+        evdev::Key::KEY_102ND => Key::IntlBackslash,
+        evdev::Key::KEY_KPSLASH => Key::Div,
+        evdev::Key::KEY_KPASTERISK => Key::Mul,
+        evdev::Key::KEY_KPMINUS => Key::Minus,
+        evdev::Key::KEY_KPPLUS => Key::Plus,
+        evdev::Key::KEY_KP0 => Key::NumpadZero,
+        evdev::Key::KEY_KP1 => Key::NumpadOne,
+        evdev::Key::KEY_KP2 => Key::NumpadTwo,
+        evdev::Key::KEY_KP3 => Key::NumpadThree,
+        evdev::Key::KEY_KP4 => Key::NumpadFour,
+        evdev::Key::KEY_KP5 => Key::NumpadFive,
+        evdev::Key::KEY_KP6 => Key::NumpadSix,
+        evdev::Key::KEY_KP7 => Key::NumpadSeven,
+        evdev::Key::KEY_KP8 => Key::NumpadEight,
+        evdev::Key::KEY_KP9 => Key::NumpadNine,
+        evdev::Key::KEY_NUMLOCK => Key::NumLock,
+        evdev::Key::KEY_KPDOT => Key::Decimal,
+        evdev::Key::KEY_KPENTER => Key::NumpadEnter,
+        evdev::Key::KEY_SCROLLLOCK => Key::ScrollLock,
+        evdev::Key::KEY_SYSRQ => Key::PrintScreen,
+        evdev::Key::KEY_PAUSE => Key::PauseBreak,
+        evdev::Key::KEY_ZENKAKUHANKAKU => Key::ZenkakuHankaku,
+        evdev::Key::KEY_HENKAN => Key::Henkan,
+        evdev::Key::KEY_MUHENKAN => Key::Muhenkan,
+        evdev::Key::KEY_KATAKANAHIRAGANA => Key::Kana,
+        evdev::Key::KEY_YEN => Key::Yen,
+        evdev::Key::KEY_RO => Key::Ro,
+        evdev::Key::KEY_HANGEUL => Key::Hangul,
+        evdev::Key::KEY_HANJA => Key::Hanja,
+        evdev::Key::KEY_KPCOMMA | evdev::Key::KEY_KPJPCOMMA => Key::NumpadComma,
+        evdev::Key::KEY_MUTE => Key::Mute,
+        evdev::Key::KEY_VOLUMEDOWN => Key::VolumeDown,
+        evdev::Key::KEY_VOLUMEUP => Key::VolumeUp,
+        evdev::Key::KEY_PREVIOUSSONG => Key::PrevTrack,
+        evdev::Key::KEY_PLAYPAUSE => Key::PlayPause,
+        evdev::Key::KEY_NEXTSONG => Key::NextTrack,
+        evdev::Key::KEY_BRIGHTNESSDOWN => Key::BrightnessDown,
+        evdev::Key::KEY_BRIGHTNESSUP => Key::BrightnessUp,
+        evdev::Key::KEY_CALC => Key::Calculator,
+        evdev::Key::KEY_MAIL => Key::Mail,
+        evdev::Key::KEY_COMPOSE | evdev::Key::KEY_MENU => Key::Menu,
+        _ => return None,
+    };
+
+    Some(key)
+}
@@ -0,0 +1,450 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+#[cfg(unix)]
+use std::{
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::{
+    key::Key,
+    leds::{LedCheck, LockKey},
+    model::{App, KbtError, KeyEventType, KeyState, RawKeyCode, StuckKey},
+    report::Report,
+};
+
+// A client that can't take a line within this long is dropped, so it can't stall the board
+#[cfg(unix)]
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+// One JSON object per line, `event` names its kind
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StreamEvent<'a> {
+    SessionStarted {
+        layout: &'a str,
+        device: Option<String>,
+        serial: Option<&'a str>,
+        keys: usize,
+    },
+    KeyPressed {
+        key: Option<Key>,
+        raw_code: Option<String>,
+        device: Option<&'a Path>,
+        // Since the session started or was reset
+        ms: u128,
+    },
+    KeyReleased {
+        key: Option<Key>,
+        raw_code: Option<String>,
+        device: Option<&'a Path>,
+        ms: u128,
+    },
+    KeyTested {
+        key: Key,
+    },
+    KeyStuck {
+        key: Key,
+    },
+    ReleaseLost {
+        key: Key,
+    },
+    Chatter {
+        key: Key,
+        count: u32,
+    },
+    Ghost {
+        key: Key,
+    },
+    LedCheck {
+        lock_key: LockKey,
+        result: LedCheck,
+    },
+    AllKeysTested,
+    Reset,
+    LayoutSelected {
+        layout: &'a str,
+        keys: usize,
+    },
+    TestStarted {
+        serial: Option<&'a str>,
+    },
+    ReportExported {
+        path: &'a Path,
+    },
+    UnitStarted {
+        serial: &'a str,
+    },
+    UnitFinished {
+        serial: &'a str,
+        passed: bool,
+        problems: &'a [String],
+    },
+    SessionEnded {
+        report: Box<Report>,
+    },
+}
+
+impl StreamEvent<'_> {
+    pub fn key_event(key_event: &KeyEventType, started_at: Instant) -> StreamEvent<'_> {
+        let event = key_event.event();
+        let (key, raw_code, device) = (
+            event.key,
+            event.raw_code.as_ref().map(RawKeyCode::to_string),
+            event.device.as_deref(),
+        );
+        let ms = event
+            .timestamp
+            .saturating_duration_since(started_at)
+            .as_millis();
+
+        match key_event {
+            KeyEventType::KeyPressed(_) => StreamEvent::KeyPressed {
+                key,
+                raw_code,
+                device,
+                ms,
+            },
+            KeyEventType::KeyReleased(_) => StreamEvent::KeyReleased {
+                key,
+                raw_code,
+                device,
+                ms,
+            },
+        }
+    }
+}
+
+// Where the stream goes, `-` is stdout and anything else a Unix socket to create
+#[derive(Clone, Debug)]
+pub enum StreamTarget {
+    Stdout,
+    Socket(PathBuf),
+}
+
+impl From<&str> for StreamTarget {
+    fn from(value: &str) -> StreamTarget {
+        match value {
+            "-" => StreamTarget::Stdout,
+            path => StreamTarget::Socket(PathBuf::from(path)),
+        }
+    }
+}
+
+pub enum EventStream {
+    Stdout,
+    #[cfg(unix)]
+    Socket {
+        path: PathBuf,
+        clients: Arc<Mutex<Vec<UnixStream>>>,
+    },
+}
+
+impl EventStream {
+    pub fn open(target: &StreamTarget) -> Result<EventStream, KbtError> {
+        match target {
+            StreamTarget::Stdout => Ok(EventStream::Stdout),
+            #[cfg(unix)]
+            StreamTarget::Socket(path) => {
+                let listener = bind(path)?;
+                let clients: Arc<Mutex<Vec<UnixStream>>> = Arc::new(Mutex::new(Vec::new()));
+                let accepted = Arc::clone(&clients);
+                thread::spawn(move || {
+                    for client in listener.incoming().flatten() {
+                        if client.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)).is_ok() {
+                            if let Ok(mut clients) = accepted.lock() {
+                                clients.push(client);
+                            }
+                        }
+                    }
+                });
+
+                Ok(EventStream::Socket {
+                    path: path.clone(),
+                    clients,
+                })
+            }
+            #[cfg(not(unix))]
+            StreamTarget::Socket(_) => Err(KbtError {
+                message: String::from("Event sockets need a Unix system, use --emit-json -"),
+            }),
+        }
+    }
+
+    pub fn emit(&self, event: &StreamEvent) {
+        let line = match serde_json::to_string(event) {
+            Ok(line) => line,
+            Err(err) => {
+                log::error!("Failed to serialize a stream event: {}", err);
+                return;
+            }
+        };
+
+        match self {
+            EventStream::Stdout => {
+                let mut stdout = io::stdout().lock();
+                if let Err(err) = writeln!(stdout, "{}", line).and_then(|_| stdout.flush()) {
+                    log::error!("Failed to write a stream event: {}", err);
+                }
+            }
+            #[cfg(unix)]
+            EventStream::Socket { clients, .. } => {
+                if let Ok(mut clients) = clients.lock() {
+                    clients.retain_mut(|client| writeln!(client, "{}", line).is_ok());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        if let EventStream::Socket { path, .. } = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+// A socket left behind by an earlier run is replaced, any other file is not
+#[cfg(unix)]
+pub fn bind(path: &Path) -> Result<UnixListener, KbtError> {
+    if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+
+    UnixListener::bind(path)
+        .map_err(|err| KbtError::from(format!("Failed to listen on {}: {}", path.display(), err)))
+}
+
+// What the test found so far, events are the differences between two snapshots
+pub struct Snapshot {
+    tested: HashSet<Key>,
+    stuck: HashSet<Key>,
+    lost_releases: HashSet<Key>,
+    chatter: HashMap<Key, u32>,
+    ghosts: HashSet<Key>,
+    led_checks: BTreeMap<LockKey, LedCheck>,
+    complete: bool,
+}
+
+impl Snapshot {
+    pub fn new(state: &App) -> Snapshot {
+        Snapshot {
+            tested: state
+                .key_states
+                .iter()
+                .filter(|(_, key_state)| {
+                    matches!(key_state, KeyState::Pressed | KeyState::Released)
+                })
+                .map(|(key, _)| *key)
+                .collect(),
+            stuck: state
+                .stuck_keys
+                .iter()
+                .filter(|(_, stuck_key)| matches!(stuck_key, StuckKey::Held))
+                .map(|(key, _)| *key)
+                .collect(),
+            lost_releases: state.lost_releases.clone(),
+            chatter: state.key_timings.chatter.clone(),
+            ghosts: state.ghost_keys.clone(),
+            led_checks: state
+                .led_test
+                .results
+                .iter()
+                .map(|(lock_key, led_check)| (*lock_key, *led_check))
+                .collect(),
+            complete: state.is_complete(),
+        }
+    }
+
+    // Emits what changed since this snapshot and moves it to the current state
+    pub fn update(&mut self, state: &App, stream: &EventStream) {
+        let current = Snapshot::new(state);
+        self.changes(&current)
+            .iter()
+            .for_each(|event| stream.emit(event));
+        *self = current;
+    }
+
+    fn changes(&self, current: &Snapshot) -> Vec<StreamEvent<'static>> {
+        let mut events: Vec<StreamEvent> = Vec::new();
+        events.extend(
+            sorted(current.tested.difference(&self.tested))
+                .map(|key| StreamEvent::KeyTested { key }),
+        );
+        events.extend(
+            sorted(current.stuck.difference(&self.stuck)).map(|key| StreamEvent::KeyStuck { key }),
+        );
+        events.extend(
+            sorted(current.lost_releases.difference(&self.lost_releases))
+                .map(|key| StreamEvent::ReleaseLost { key }),
+        );
+        events.extend(
+            sorted(
+                current
+                    .chatter
+                    .iter()
+                    .filter(|(key, count)| self.chatter.get(key) != Some(count))
+                    .map(|(key, _)| key),
+            )
+            .map(|key| StreamEvent::Chatter {
+                key,
+                count: current.chatter[&key],
+            }),
+        );
+        events.extend(
+            sorted(current.ghosts.difference(&self.ghosts)).map(|key| StreamEvent::Ghost { key }),
+        );
+        events.extend(
+            current
+                .led_checks
+                .iter()
+                .filter(|(lock_key, led_check)| self.led_checks.get(lock_key) != Some(led_check))
+                .map(|(lock_key, led_check)| StreamEvent::LedCheck {
+                    lock_key: *lock_key,
+                    result: *led_check,
+                }),
+        );
+        if current.complete && !self.complete {
+            events.push(StreamEvent::AllKeysTested);
+        }
+
+        events
+    }
+}
+
+fn sorted<'a>(keys: impl Iterator<Item = &'a Key>) -> impl Iterator<Item = Key> {
+    let mut keys: Vec<Key> = keys.copied().collect();
+    keys.sort_by_key(|key| format!("{:?}", key));
+
+    keys.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        leds::{LedSource, LedTest},
+        model::{KeyBackend, KeySize, KeyTimings, KeyUI, Legends, TestMode},
+        polling::PollingStats,
+        prepare_layout,
+    };
+
+    fn app(keys: &[Key]) -> App {
+        let row = keys
+            .iter()
+            .map(|key| KeyUI {
+                key: *key,
+                size: KeySize::U1,
+                size_correction: None,
+                vertical_key_part: None,
+            })
+            .collect();
+        let (_, receiver) = mpsc::channel();
+
+        App {
+            key_states: HashMap::new(),
+            event_receiver: receiver,
+            layout: prepare_layout(
+                String::from("test"),
+                vec![row],
+                Legends::default(),
+                HashMap::new(),
+            ),
+            locale: None,
+            last_key_event: None,
+            unmapped_key_states: HashMap::new(),
+            device: None,
+            key_timings: KeyTimings::default(),
+            polling: PollingStats::default(),
+            backend: KeyBackend::Generic,
+            stuck_threshold: Duration::from_secs(1),
+            stuck_keys: HashMap::new(),
+            lost_releases: HashSet::new(),
+            leds: None,
+            led_source: LedSource::Unavailable,
+            led_test: LedTest::default(),
+            modifier_matrix: None,
+            ghost_keys: HashSet::new(),
+            mode: TestMode::Keys,
+            compare: None,
+            raw_codes: HashMap::new(),
+            started_at: Instant::now(),
+            serial: None,
+            batch: None,
+            event_stream: None,
+        }
+    }
+
+    fn changes(before: &App, after: &App) -> Vec<Value> {
+        Snapshot::new(before)
+            .changes(&Snapshot::new(after))
+            .iter()
+            .map(|event| serde_json::to_value(event).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn emits_what_changed_between_two_states() {
+        let before = app(&[Key::A, Key::B, Key::CapsLock]);
+        let mut after = app(&[Key::A, Key::B, Key::CapsLock]);
+        after.key_states.insert(Key::B, KeyState::Released);
+        after.key_states.insert(Key::A, KeyState::Pressed);
+        after.stuck_keys.insert(Key::A, StuckKey::Held);
+        after.key_timings.chatter.insert(Key::B, 1);
+        after.key_timings.chatter.insert(Key::A, 2);
+        after
+            .led_test
+            .results
+            .insert(LockKey::Caps, LedCheck::DidNotFlip);
+
+        assert_eq!(
+            changes(&before, &after),
+            vec![
+                json!({"event": "key_tested", "key": "A"}),
+                json!({"event": "key_tested", "key": "B"}),
+                json!({"event": "key_stuck", "key": "A"}),
+                json!({"event": "chatter", "key": "A", "count": 2}),
+                json!({"event": "chatter", "key": "B", "count": 1}),
+                json!({"event": "led_check", "lock_key": "Caps", "result": "did_not_flip"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn emits_only_new_chatter_and_completion_once() {
+        let mut before = app(&[Key::A, Key::B]);
+        before.key_states.insert(Key::A, KeyState::Released);
+        before.key_timings.chatter.insert(Key::A, 1);
+        let mut after = app(&[Key::A, Key::B]);
+        after.key_states.insert(Key::A, KeyState::Released);
+        after.key_states.insert(Key::B, KeyState::Released);
+        after.key_timings.chatter.insert(Key::A, 1);
+
+        assert_eq!(
+            changes(&before, &after),
+            vec![
+                json!({"event": "key_tested", "key": "B"}),
+                json!({"event": "all_keys_tested"}),
+            ]
+        );
+        assert!(changes(&after, &after).is_empty());
+    }
+}
@@ -1,16 +1,20 @@
-use std::sync::{mpsc::Sender, Arc, Mutex};
+use std::{
+    collections::HashSet,
+    sync::{mpsc::Sender, Arc, Mutex},
+    time::Instant,
+};
 
-use device_query_revamped::{CallbackGuard, DeviceEvents, DeviceState, Keycode};
+use device_query_revamped::{CallbackGuard, DeviceEvents, DeviceQuery, DeviceState, Keycode};
 
 use crate::{
     key::Key,
-    model::{AppEvent, KeyEventType},
+    model::{AppEvent, KeyEvent, KeyEventType},
 };
 
 pub struct GenericKeyBackend;
 
 type KeycodeCallback = Box<dyn Fn(&Keycode) + Send + Sync + 'static>;
-type KeyStreamGuard = CallbackGuard<KeycodeCallback>;
+pub type KeyStreamGuard = CallbackGuard<KeycodeCallback>;
 
 impl GenericKeyBackend {
     pub fn subscribe(sender: &Sender<AppEvent>) -> (KeyStreamGuard, KeyStreamGuard) {
@@ -21,9 +25,9 @@ impl GenericKeyBackend {
             let _ = shared_sender
                 .lock()
                 .unwrap()
-                .send(AppEvent::KeyEvent(KeyEventType::KeyReleased(map_keycode(
-                    keycode,
-                ))))
+                .send(AppEvent::KeyEvent(KeyEventType::KeyReleased(
+                    make_key_event(keycode),
+                )))
                 .map_err(|err| log::error!("Key down channel died {}", err));
         }));
 
@@ -33,14 +37,31 @@ impl GenericKeyBackend {
             let _ = shared_sender
                 .lock()
                 .unwrap()
-                .send(AppEvent::KeyEvent(KeyEventType::KeyPressed(map_keycode(
-                    keycode,
-                ))))
+                .send(AppEvent::KeyEvent(KeyEventType::KeyPressed(
+                    make_key_event(keycode),
+                )))
                 .map_err(|err| log::error!("Key down channel died {}", err));
         }));
 
         (key_up_guard, key_down_guard)
     }
+
+    pub fn pressed_keys() -> HashSet<Key> {
+        DeviceState::new()
+            .get_keys()
+            .iter()
+            .map(map_keycode)
+            .collect()
+    }
+}
+
+fn make_key_event(keycode: &Keycode) -> KeyEvent {
+    KeyEvent {
+        key: Some(map_keycode(keycode)),
+        raw_code: None,
+        device: None,
+        timestamp: Instant::now(),
+    }
 }
 
 fn map_keycode(keycode: &Keycode) -> Key {
@@ -101,7 +122,7 @@ fn map_keycode(keycode: &Keycode) -> Key {
         Keycode::RShift => Key::RightShift,
         Keycode::LAlt => Key::LeftAlt,
         Keycode::RAlt => Key::RightAlt,
-        Keycode::Command => Key::Command,
+        Keycode::Command => Key::LeftSuper,
         Keycode::LMeta => Key::LeftSuper,
         Keycode::RMeta => Key::RightSuper,
         Keycode::Enter => Key::Return,
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use crate::key::Key;
+
+// Row and column of a switch in the board's wiring matrix
+pub type MatrixPosition = (u8, u8);
+
+// Without working diodes, current sneaks through three held corners of a rectangle in the matrix and
+// the controller reads the fourth corner as pressed too
+pub fn phantom_positions(
+    matrix: &HashMap<Key, MatrixPosition>,
+    held_keys: &[Key],
+) -> Vec<MatrixPosition> {
+    let held: Vec<MatrixPosition> = held_keys
+        .iter()
+        .filter_map(|key| matrix.get(key).copied())
+        .collect();
+
+    let mut phantoms: Vec<MatrixPosition> = Vec::new();
+    for (row, col) in &held {
+        for (_, other_col) in held.iter().filter(|(r, c)| r == row && c != col) {
+            for (other_row, _) in held.iter().filter(|(r, c)| c == col && r != row) {
+                let phantom = (*other_row, *other_col);
+                if !phantoms.contains(&phantom) {
+                    phantoms.push(phantom);
+                }
+            }
+        }
+    }
+
+    phantoms
+}
+
+// A key whose press completes a rectangle with three keys that were already held is most likely a ghost
+pub fn is_ghost_press(matrix: &HashMap<Key, MatrixPosition>, held_keys: &[Key], key: Key) -> bool {
+    matrix
+        .get(&key)
+        .is_some_and(|position| phantom_positions(matrix, held_keys).contains(position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix() -> HashMap<Key, MatrixPosition> {
+        HashMap::from([
+            (Key::Q, (0, 0)),
+            (Key::W, (0, 1)),
+            (Key::E, (0, 2)),
+            (Key::A, (1, 0)),
+            (Key::S, (1, 1)),
+        ])
+    }
+
+    #[test]
+    fn fourth_corner_of_a_held_rectangle_is_a_ghost() {
+        let held = [Key::Q, Key::W, Key::A];
+
+        assert_eq!(phantom_positions(&matrix(), &held), vec![(1, 1)]);
+        assert!(is_ghost_press(&matrix(), &held, Key::S));
+        assert!(!is_ghost_press(&matrix(), &held, Key::E));
+    }
+
+    #[test]
+    fn keys_in_one_row_make_no_ghosts() {
+        let held = [Key::Q, Key::W];
+
+        assert!(phantom_positions(&matrix(), &held).is_empty());
+        assert!(!is_ghost_press(&matrix(), &held, Key::E));
+    }
+
+    #[test]
+    fn keys_off_the_matrix_are_ignored() {
+        let held = [Key::Q, Key::W, Key::Z];
+
+        assert!(phantom_positions(&matrix(), &held).is_empty());
+        assert!(!is_ghost_press(
+            &matrix(),
+            &[Key::Q, Key::W, Key::A],
+            Key::Z
+        ));
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::{App, KbtError},
+    report::Report,
+};
+
+// A key degraded when its chatter rate grew by this many percentage points
+const CHATTER_RATE_INCREASE: f64 = 1.0;
+// or its hold times spread out by this much, and to over 1.5 times what they were
+const HOLD_SPREAD_INCREASE_MS: f64 = 10.0;
+
+// One line of the JSON-lines history file
+#[derive(Deserialize, Serialize)]
+pub struct Session {
+    pub id: u32,
+    // Seconds since the Unix epoch
+    pub finished_at: u64,
+    pub kbt_version: String,
+    pub serial: Option<String>,
+    pub device: Option<String>,
+    pub layout: String,
+    pub duration_secs: u64,
+    pub tested_keys: usize,
+    pub untested_keys: Vec<String>,
+    pub stuck_keys: Vec<String>,
+    pub lost_releases: Vec<String>,
+    pub chatter_keys: Vec<String>,
+    pub ghost_keys: Vec<String>,
+    pub keys: BTreeMap<String, KeyStats>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct KeyStats {
+    pub presses: u32,
+    pub chatter: u32,
+    pub hold_avg_ms: Option<f64>,
+    // Standard deviation of the hold times
+    pub hold_spread_ms: Option<f64>,
+}
+
+impl Session {
+    fn new(id: u32, state: &App) -> Session {
+        let report = Report::new(state);
+        let names = |keys: &[crate::key::Key]| -> Vec<String> {
+            keys.iter().map(|key| format!("{:?}", key)).collect()
+        };
+
+        let keys: BTreeMap<String, KeyStats> = state
+            .layout
+            .keys()
+            .into_iter()
+            .filter_map(|key| {
+                let presses = state.key_timings.presses.get(&key).copied()?;
+                let (hold_avg_ms, hold_spread_ms) = state
+                    .key_timings
+                    .hold_history
+                    .get(&key)
+                    .map_or((None, None), |hold_times| hold_spread(hold_times));
+                Some((
+                    format!("{:?}", key),
+                    KeyStats {
+                        presses,
+                        chatter: state.key_timings.chatter.get(&key).copied().unwrap_or(0),
+                        hold_avg_ms,
+                        hold_spread_ms,
+                    },
+                ))
+            })
+            .collect();
+
+        Session {
+            id,
+            finished_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since_epoch| since_epoch.as_secs()),
+            kbt_version: report.kbt_version.to_string(),
+            serial: report.serial,
+            device: report.device,
+            layout: report.layout,
+            duration_secs: report.duration_secs,
+            tested_keys: report.tested_keys.len(),
+            untested_keys: names(&report.untested_keys),
+            stuck_keys: names(&report.stuck_keys),
+            lost_releases: names(&report.lost_releases),
+            chatter_keys: names(&report.chatter_keys),
+            ghost_keys: names(&report.ghost_keys),
+            keys,
+        }
+    }
+
+    pub fn failures(&self) -> usize {
+        self.stuck_keys.len()
+            + self.lost_releases.len()
+            + self.chatter_keys.len()
+            + self.ghost_keys.len()
+    }
+
+    // Boards are told apart by serial, the input device name otherwise
+    fn board(&self) -> &str {
+        self.serial
+            .as_deref()
+            .or(self.device.as_deref())
+            .unwrap_or("-")
+    }
+}
+
+pub fn read(path: &Path) -> Result<Vec<Session>, KbtError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(KbtError::from(format!(
+                "Failed to read {}: {}",
+                path.display(),
+                err
+            )))
+        }
+    };
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            serde_json::from_str(line).map_err(|err| {
+                KbtError::from(format!("{} line {}: {}", path.display(), idx + 1, err))
+            })
+        })
+        .collect()
+}
+
+pub fn append(path: &Path, state: &App) -> Result<(), KbtError> {
+    let id = read(path)?
+        .iter()
+        .map(|session| session.id)
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let line = serde_json::to_string(&Session::new(id, state))?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| format!("Failed to open {}: {}", path.display(), err))?;
+    writeln!(file, "{}", line)?;
+
+    Ok(())
+}
+
+pub fn list(path: &Path) -> Result<(), KbtError> {
+    let sessions = read(path)?;
+    if sessions.is_empty() {
+        println!("no sessions in {}", path.display());
+        return Ok(());
+    }
+
+    println!(
+        "{:>4}  {:<16}  {:<24}  {:<20}  {:>6}  {:>8}",
+        "id", "finished (UTC)", "board", "layout", "tested", "failures"
+    );
+    for session in &sessions {
+        println!(
+            "{:>4}  {:<16}  {:<24}  {:<20}  {:>6}  {:>8}",
+            format!("#{}", session.id),
+            format_timestamp(session.finished_at),
+            session.board(),
+            session.layout,
+            session.tested_keys,
+            session.failures()
+        );
+    }
+
+    Ok(())
+}
+
+// `a` and `b` are session ids like `#12`, or serials that stand for the latest session of that board
+pub fn compare(path: &Path, a: &str, b: &str) -> Result<(), KbtError> {
+    let sessions = read(path)?;
+    let before = find_session(&sessions, a)?;
+    let after = find_session(&sessions, b)?;
+
+    println!(
+        "#{} ({}, {}) -> #{} ({}, {})",
+        before.id,
+        before.board(),
+        format_timestamp(before.finished_at),
+        after.id,
+        after.board(),
+        format_timestamp(after.finished_at)
+    );
+
+    let degraded = degraded(before, after);
+    if degraded.is_empty() {
+        println!("no keys degraded");
+    } else {
+        degraded.iter().for_each(|line| println!("{}", line));
+    }
+
+    Ok(())
+}
+
+// Keys that got worse from one session to the other, one line each
+fn degraded(before: &Session, after: &Session) -> Vec<String> {
+    let mut degraded: Vec<String> = Vec::new();
+    for (key, after_stats) in &after.keys {
+        let Some(before_stats) = before.keys.get(key) else {
+            continue;
+        };
+
+        let (before_rate, after_rate) = (chatter_rate(before_stats), chatter_rate(after_stats));
+        if after_rate - before_rate >= CHATTER_RATE_INCREASE {
+            degraded.push(format!(
+                "{:<14} chatter {:.1}% -> {:.1}%",
+                key, before_rate, after_rate
+            ));
+        }
+
+        if let (Some(before_spread), Some(after_spread)) =
+            (before_stats.hold_spread_ms, after_stats.hold_spread_ms)
+        {
+            if after_spread - before_spread >= HOLD_SPREAD_INCREASE_MS
+                && after_spread > before_spread * 1.5
+            {
+                degraded.push(format!(
+                    "{:<14} hold time spread {:.0} ms -> {:.0} ms",
+                    key, before_spread, after_spread
+                ));
+            }
+        }
+    }
+
+    let failures = [
+        ("stuck", &before.stuck_keys, &after.stuck_keys),
+        ("lost release", &before.lost_releases, &after.lost_releases),
+        ("ghost", &before.ghost_keys, &after.ghost_keys),
+    ];
+    for (failure, before_keys, after_keys) in failures {
+        for key in after_keys.iter().filter(|key| !before_keys.contains(key)) {
+            degraded.push(format!("{:<14} newly {}", key, failure));
+        }
+    }
+
+    degraded
+}
+
+// Ids are written `#12`, so a serial made of digits can't be taken for one
+fn find_session<'a>(sessions: &'a [Session], id_or_serial: &str) -> Result<&'a Session, KbtError> {
+    match id_or_serial.strip_prefix('#') {
+        Some(id) => {
+            let id = id.parse::<u32>().map_err(|_| KbtError {
+                message: format!("{} is not a session id, ids look like #12", id_or_serial),
+            })?;
+            sessions
+                .iter()
+                .find(|session| session.id == id)
+                .ok_or(KbtError {
+                    message: format!("No session {} in the history", id_or_serial),
+                })
+        }
+        None => sessions
+            .iter()
+            .rev()
+            .find(|session| session.serial.as_deref() == Some(id_or_serial))
+            .ok_or(KbtError {
+                message: format!("No session with serial {} in the history", id_or_serial),
+            }),
+    }
+}
+
+// Percent of presses that chattered
+fn chatter_rate(stats: &KeyStats) -> f64 {
+    if stats.presses == 0 {
+        0.0
+    } else {
+        f64::from(stats.chatter) * 100.0 / f64::from(stats.presses)
+    }
+}
+
+fn hold_spread(hold_times: &[Duration]) -> (Option<f64>, Option<f64>) {
+    if hold_times.is_empty() {
+        return (None, None);
+    }
+
+    let millis: Vec<f64> = hold_times
+        .iter()
+        .map(|hold_time| hold_time.as_secs_f64() * 1000.0)
+        .collect();
+    let avg = millis.iter().sum::<f64>() / millis.len() as f64;
+    let variance = millis.iter().map(|ms| (ms - avg).powi(2)).sum::<f64>() / millis.len() as f64;
+
+    (Some(avg), Some(variance.sqrt()))
+}
+
+// UTC, without pulling in a date crate
+fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let (hours, minutes) = ((secs % 86_400) / 3600, (secs % 3600) / 60);
+
+    // Days to civil date, from Howard Hinnant's date algorithms
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year, month, day, hours, minutes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(id: u32, serial: Option<&str>) -> Session {
+        Session {
+            id,
+            finished_at: 0,
+            kbt_version: String::from("test"),
+            serial: serial.map(String::from),
+            device: None,
+            layout: String::from("60%"),
+            duration_secs: 0,
+            tested_keys: 0,
+            untested_keys: Vec::new(),
+            stuck_keys: Vec::new(),
+            lost_releases: Vec::new(),
+            chatter_keys: Vec::new(),
+            ghost_keys: Vec::new(),
+            keys: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn finds_sessions_by_id_or_latest_serial() {
+        let sessions = [
+            session(1, Some("2")),
+            session(2, Some("SN7")),
+            session(3, Some("2")),
+        ];
+
+        let id = |id_or_serial: &str| find_session(&sessions, id_or_serial).map(|s| s.id);
+        assert_eq!(id("#2").ok(), Some(2));
+        // A serial of digits is never an id
+        assert_eq!(id("2").ok(), Some(3));
+        assert_eq!(id("SN7").ok(), Some(2));
+        assert!(id("#4").is_err());
+        assert!(id("#SN7").is_err());
+        assert!(id("SN8").is_err());
+    }
+
+    #[test]
+    fn formats_timestamps_as_utc() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00");
+        assert_eq!(format_timestamp(1_709_251_199), "2024-02-29 23:59");
+        assert_eq!(format_timestamp(1_735_689_600), "2025-01-01 00:00");
+    }
+
+    fn key_stats(presses: u32, chatter: u32, hold_spread_ms: Option<f64>) -> KeyStats {
+        KeyStats {
+            presses,
+            chatter,
+            hold_avg_ms: None,
+            hold_spread_ms,
+        }
+    }
+
+    #[test]
+    fn reports_keys_that_got_worse() {
+        let mut before = session(1, None);
+        let mut after = session(2, None);
+        let keys = [
+            // One chattering press in 50 is 2 points more
+            ("A", key_stats(50, 0, None), key_stats(50, 1, None)),
+            ("B", key_stats(100, 0, None), key_stats(100, 0, None)),
+            // 8 ms more is under the threshold
+            (
+                "C",
+                key_stats(10, 0, Some(4.0)),
+                key_stats(10, 0, Some(12.0)),
+            ),
+            // 10 ms more but not 1.5 times as spread
+            (
+                "D",
+                key_stats(10, 0, Some(30.0)),
+                key_stats(10, 0, Some(40.0)),
+            ),
+            (
+                "E",
+                key_stats(10, 0, Some(10.0)),
+                key_stats(10, 0, Some(20.0)),
+            ),
+        ];
+        for (key, before_stats, after_stats) in keys {
+            before.keys.insert(String::from(key), before_stats);
+            after.keys.insert(String::from(key), after_stats);
+        }
+        before.stuck_keys = vec![String::from("F")];
+        after.stuck_keys = vec![String::from("F"), String::from("G")];
+
+        let lines = degraded(&before, &after);
+        let keys: Vec<&str> = lines
+            .iter()
+            .filter_map(|line| line.split_whitespace().next())
+            .collect();
+        assert_eq!(keys, ["A", "E", "G"]);
+        assert!(lines[0].contains("chatter 0.0% -> 2.0%"));
+        assert!(lines[1].contains("hold time spread 10 ms -> 20 ms"));
+        assert!(lines[2].contains("newly stuck"));
+        assert!(degraded(&after, &after).is_empty());
+    }
+}
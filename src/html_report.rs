@@ -0,0 +1,190 @@
+use std::{collections::BTreeMap, fs, path::Path, time::Duration};
+
+use serde::Serialize;
+
+use crate::{
+    key::Key,
+    model::{App, KbtError},
+    report::Report,
+    svg::{self, escape, KeyMark},
+};
+
+// Per-key details shown when hovering a key of the HTML report
+#[derive(Serialize)]
+struct KeyDetails {
+    key: Key,
+    state: &'static str,
+    presses: u32,
+    hold_ms: Option<HoldStats>,
+    chatter: u32,
+    raw_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct HoldStats {
+    min: u128,
+    avg: u128,
+    max: u128,
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { text-align: left; padding: 2px 12px 2px 0; }
+#board g[data-key] { cursor: pointer; }
+#board g[data-key]:hover path { stroke: #06c; stroke-width: 2; }
+#details { min-height: 6em; padding: 0.5em 1em; background: #f4f4f4; border-radius: 4px; }
+.problem { color: #b00; }
+";
+
+// Details are built from text nodes, raw codes come from the device and are never parsed as markup
+const SCRIPT: &str = "
+const details = document.getElementById('details');
+const line = (label, value) => {
+  const row = document.createElement('div');
+  row.textContent = `${label}: ${value}`;
+  return row;
+};
+const show = (key) => {
+  const info = KEYS[key];
+  if (!info) return;
+  const hold = info.hold_ms
+    ? `${info.hold_ms.min} / ${info.hold_ms.avg} / ${info.hold_ms.max} ms`
+    : 'never released';
+  const title = document.createElement('div');
+  const name = document.createElement('b');
+  name.textContent = info.key;
+  title.append(name, ` ${info.state}`);
+  details.replaceChildren(
+    title,
+    line('presses', info.presses),
+    line('hold min / avg / max', hold),
+    line('chatter events', info.chatter),
+    line('raw codes', info.raw_codes.join(', ') || 'none'),
+  );
+};
+document.querySelectorAll('#board g[data-key]').forEach((key) => {
+  key.addEventListener('mouseenter', () => show(key.dataset.key));
+});
+";
+
+// Single file report, styles, script and key data are inlined so it opens offline
+pub fn write(path: &Path, state: &App) -> Result<(), KbtError> {
+    let report = Report::new(state);
+    let marks = svg::key_marks(state);
+
+    let key_details: Vec<KeyDetails> = state
+        .layout
+        .keys()
+        .into_iter()
+        .map(|key| KeyDetails {
+            key,
+            state: marks.get(&key).unwrap_or(&KeyMark::Untested).label(),
+            presses: state.key_timings.presses.get(&key).copied().unwrap_or(0),
+            hold_ms: state
+                .key_timings
+                .hold_history
+                .get(&key)
+                .and_then(|hold_times| hold_stats(hold_times)),
+            chatter: state.key_timings.chatter.get(&key).copied().unwrap_or(0),
+            raw_codes: state
+                .raw_codes
+                .get(&key)
+                .map(|raw_codes| raw_codes.iter().cloned().collect())
+                .unwrap_or_default(),
+        })
+        .collect();
+    let keys_json = serde_json::to_string(
+        &key_details
+            .iter()
+            .map(|details| (format!("{:?}", details.key), details))
+            .collect::<BTreeMap<String, &KeyDetails>>(),
+    )?;
+
+    let metadata = [
+        ("layout", report.layout.clone()),
+        (
+            "device",
+            report
+                .device
+                .clone()
+                .unwrap_or(String::from("all keyboards")),
+        ),
+        ("backend", format!("{:?}", report.backend).to_lowercase()),
+        ("duration", format_duration(report.duration_secs)),
+        ("kbt version", report.kbt_version.to_string()),
+        (
+            "tested keys",
+            format!(
+                "{} of {}",
+                report.tested_keys.len(),
+                report.tested_keys.len() + report.untested_keys.len()
+            ),
+        ),
+    ];
+    let problems = [
+        ("untested", &report.untested_keys),
+        ("stuck", &report.stuck_keys),
+        ("lost releases", &report.lost_releases),
+        ("chatter", &report.chatter_keys),
+        ("ghosts", &report.ghost_keys),
+    ];
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!(
+        "<title>kbt report: {}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        escape(&report.layout),
+        STYLE
+    ));
+    html.push_str(&format!(
+        "<h1>kbt report: {}</h1>\n<table>\n",
+        escape(&report.layout)
+    ));
+    for (name, value) in metadata {
+        html.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            name,
+            escape(&value)
+        ));
+    }
+    for (name, keys) in problems.iter().filter(|(_, keys)| !keys.is_empty()) {
+        let keys: Vec<String> = keys.iter().map(|key| format!("{:?}", key)).collect();
+        html.push_str(&format!(
+            "<tr class=\"problem\"><th>{}</th><td>{}</td></tr>\n",
+            name,
+            keys.join(" ")
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<div id=\"board\">\n");
+    html.push_str(&svg::render(&state.layout, state.locale, Some(&marks)));
+    html.push_str("</div>\n<p>Hover a key for its details.</p>\n<div id=\"details\"></div>\n");
+
+    html.push_str("<script>\nconst KEYS = ");
+    // A raw code can't close the script tag early
+    html.push_str(&keys_json.replace("</", "<\\/"));
+    html.push_str(";\n");
+    html.push_str(SCRIPT);
+    html.push_str("</script>\n</body>\n</html>\n");
+
+    fs::write(path, html)
+        .map_err(|err| KbtError::from(format!("Failed to write {}: {}", path.display(), err)))
+}
+
+fn hold_stats(hold_times: &[Duration]) -> Option<HoldStats> {
+    let min = hold_times.iter().min()?;
+    let max = hold_times.iter().max()?;
+    let total: Duration = hold_times.iter().sum();
+
+    Some(HoldStats {
+        min: min.as_millis(),
+        avg: total.as_millis() / hold_times.len() as u128,
+        max: max.as_millis(),
+    })
+}
+
+fn format_duration(secs: u64) -> String {
+    format!("{}m {:02}s", secs / 60, secs % 60)
+}
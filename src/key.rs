@@ -1,6 +1,10 @@
 use std::fmt;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+use crate::model::Legends;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum Key {
     Q,
     W,
@@ -53,6 +57,7 @@ pub enum Key {
     Comma,
     Period,
     QuestionMark,
+    IntlBackslash,
 
     // modifiers
     Esc,
@@ -60,6 +65,8 @@ pub enum Key {
     CapsLock,
     LeftShift,
     LeftCtrl,
+    // Command is what the Apple keyboards print on it, older layout files still use it
+    #[serde(alias = "Command")]
     LeftSuper,
     LeftAlt,
     Spacebar,
@@ -76,7 +83,7 @@ pub enum Key {
     End,
     PgDown,
 
-    Command,
+    Fn,
 
     // arrows
     ArrowUp,
@@ -97,6 +104,43 @@ pub enum Key {
     F10,
     F11,
     F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+
+    // media and system
+    Mute,
+    VolumeDown,
+    VolumeUp,
+    PrevTrack,
+    PlayPause,
+    NextTrack,
+    BrightnessDown,
+    BrightnessUp,
+    Calculator,
+    Mail,
+    Menu,
+
+    // JIS
+    ZenkakuHankaku,
+    Henkan,
+    Muhenkan,
+    Kana,
+    Yen,
+    Ro,
+
+    // Korean
+    Hangul,
+    Hanja,
 
     // misc
     Separator,
@@ -112,6 +156,7 @@ pub enum Key {
     Plus,
     Decimal,
     NumpadEnter,
+    NumpadComma,
     NumpadZero,
     NumpadOne,
     NumpadTwo,
@@ -145,6 +190,7 @@ impl fmt::Display for Key {
             Key::Comma => write!(f, "<,"),
             Key::Period => write!(f, ">."),
             Key::QuestionMark => write!(f, "?/"),
+            Key::IntlBackslash => write!(f, "<>"),
             Key::One => write!(f, "1"),
             Key::Two => write!(f, "2"),
             Key::Three => write!(f, "3"),
@@ -183,7 +229,99 @@ impl fmt::Display for Key {
             Key::NumpadSeven => write!(f, "7"),
             Key::NumpadEight => write!(f, "8"),
             Key::NumpadNine => write!(f, "9"),
+            Key::ZenkakuHankaku => write!(f, "Z/H"),
+            Key::Henkan => write!(f, "Hen"),
+            Key::Muhenkan => write!(f, "Muh"),
+            Key::Yen => write!(f, "|¥"),
+            Key::Ro => write!(f, "_\\"),
+            Key::Hangul => write!(f, "H/Y"),
+            Key::Hanja => write!(f, "Hnj"),
+            Key::NumpadComma => write!(f, ","),
+            Key::Mute => write!(f, "Mut"),
+            Key::VolumeDown => write!(f, "V-"),
+            Key::VolumeUp => write!(f, "V+"),
+            Key::PrevTrack => write!(f, "|<<"),
+            Key::PlayPause => write!(f, ">||"),
+            Key::NextTrack => write!(f, ">>|"),
+            Key::BrightnessDown => write!(f, "B-"),
+            Key::BrightnessUp => write!(f, "B+"),
+            Key::Calculator => write!(f, "Clc"),
+            Key::Mail => write!(f, "@"),
             _ => write!(f, "{:?}", self),
         }
     }
 }
+
+impl Key {
+    pub fn legend(&self, legends: Legends) -> String {
+        match legends {
+            Legends::Standard => self.to_string(),
+            Legends::Mac => self
+                .mac_legend()
+                .map_or_else(|| self.to_string(), String::from),
+            Legends::Jis => self
+                .jis_legend()
+                .map_or_else(|| self.to_string(), String::from),
+            Legends::Abnt2 => self
+                .abnt2_legend()
+                .map_or_else(|| self.to_string(), String::from),
+        }
+    }
+
+    fn mac_legend(&self) -> Option<&'static str> {
+        match self {
+            Key::Esc => Some("esc"),
+            Key::Tab => Some("⇥"),
+            Key::CapsLock => Some("⇪"),
+            Key::LeftShift | Key::RightShift => Some("⇧"),
+            Key::LeftCtrl | Key::RightCtrl => Some("⌃"),
+            Key::LeftAlt | Key::RightAlt => Some("⌥"),
+            Key::LeftSuper | Key::RightSuper => Some("⌘"),
+            Key::Fn => Some("fn"),
+            Key::Backspace => Some("⌫"),
+            Key::Return => Some("↩"),
+            Key::IntlBackslash => Some("§"),
+            _ => None,
+        }
+    }
+
+    fn jis_legend(&self) -> Option<&'static str> {
+        match self {
+            Key::Grave => Some("半/全"),
+            Key::Two => Some("\"2"),
+            Key::Six => Some("&6"),
+            Key::Seven => Some("'7"),
+            Key::Eight => Some("(8"),
+            Key::Nine => Some(")9"),
+            Key::Zero => Some("0"),
+            Key::Hyphen => Some("=-"),
+            Key::Equal => Some("~^"),
+            Key::LeftBracket => Some("`@"),
+            Key::RightBracket => Some("{["),
+            Key::SemiColon => Some("+;"),
+            Key::Apostrophe => Some("*:"),
+            Key::Backslash => Some("}]"),
+            Key::CapsLock => Some("Eisu"),
+            _ => None,
+        }
+    }
+
+    fn abnt2_legend(&self) -> Option<&'static str> {
+        match self {
+            Key::Grave => Some("\"'"),
+            Key::Six => Some("¨6"),
+            Key::Equal => Some("+="),
+            Key::LeftBracket => Some("`´"),
+            Key::RightBracket => Some("{["),
+            Key::SemiColon => Some("Ç"),
+            Key::Apostrophe => Some("^~"),
+            Key::Backslash => Some("}]"),
+            Key::IntlBackslash => Some("|\\"),
+            Key::QuestionMark => Some(":;"),
+            Key::Ro => Some("?/"),
+            Key::Decimal => Some(","),
+            Key::NumpadComma => Some("."),
+            _ => None,
+        }
+    }
+}
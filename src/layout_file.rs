@@ -0,0 +1,99 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ghosting::MatrixPosition,
+    key::Key,
+    model::{KbtError, KeySize, KeyUI, KeyboardLayout, Legends, VerticalKeyPart},
+};
+
+// Layouts kbt doesn't ship, read from a JSON file. Keys use the same names, sizes and vertical parts
+// as the built-in layouts, `matrix` optionally places the key in the board's switch matrix.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct LayoutFile {
+    pub name: String,
+    #[serde(default)]
+    pub legends: Legends,
+    pub rows: Vec<Vec<LayoutFileKey>>,
+    // Keys an import found no kbt key for, they are left as gaps
+    #[serde(skip)]
+    pub unbound_keys: usize,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct LayoutFileKey {
+    pub key: Key,
+    pub size: KeySize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correction: Option<i16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part: Option<VerticalKeyPart>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<MatrixPosition>,
+}
+
+impl LayoutFile {
+    pub fn load(path: &Path) -> Result<LayoutFile, KbtError> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+
+        serde_json::from_str(&content).map_err(|err| {
+            KbtError::from(format!("Invalid layout file {}: {}", path.display(), err))
+        })
+    }
+
+    // Built-in layouts as a starting point for a layout file
+    pub fn from_layout(layout: &KeyboardLayout) -> LayoutFile {
+        LayoutFile {
+            name: layout.name.clone(),
+            legends: layout.legends,
+            rows: layout
+                .rows
+                .iter()
+                .map(|row| {
+                    row.keys
+                        .iter()
+                        .map(|key| LayoutFileKey {
+                            key: key.key,
+                            size: key.size,
+                            correction: key.size_correction,
+                            part: key.vertical_key_part,
+                            matrix: layout.matrix.get(&key.key).copied(),
+                        })
+                        .collect()
+                })
+                .collect(),
+            unbound_keys: 0,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), KbtError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|err| KbtError::from(format!("Failed to write {}: {}", path.display(), err)))
+    }
+
+    pub fn key_rows(&self) -> Vec<Vec<KeyUI>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|key| KeyUI {
+                        key: key.key,
+                        size: key.size,
+                        size_correction: key.correction,
+                        vertical_key_part: key.part,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn matrix(&self) -> HashMap<Key, MatrixPosition> {
+        self.rows
+            .iter()
+            .flatten()
+            .filter_map(|key| key.matrix.map(|position| (key.key, position)))
+            .collect()
+    }
+}
@@ -0,0 +1,425 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{
+    key::Key,
+    keyboard100, keyboard60, keyboard80, keyboard_abnt2, keyboard_jis, keyboard_korean,
+    layout_file::{LayoutFile, LayoutFileKey},
+    model::{KbtError, KeyEvent, KeySize, KeyUI, KeyboardLayout, Legends, VerticalKeyPart},
+    prepare_layout,
+};
+
+// Width of the alphanumeric block of a standard board, in terminal cells
+const MAIN_BLOCK_CELLS: i16 = 75;
+// Rows further off than this from the standard total are left alone, e.g. the rows of a 40% board
+const MAX_ROW_STRETCH_CELLS: i16 = 15;
+
+// Layout recorded by pressing every key from left to right, row by row. Ctrl+N ends the row,
+// sizes come from the built-in layouts.
+pub struct LayoutLearner {
+    pub path: PathBuf,
+    pub rows: Vec<Vec<Key>>,
+    pub row: Vec<Key>,
+    pub message: Option<String>,
+    // Ctrl while it is held down, and whether its press was recorded
+    held_ctrl: Option<(Key, bool)>,
+}
+
+// Keys of one cluster are drawn next to each other, a gap goes between clusters
+#[derive(PartialEq)]
+enum Cluster {
+    Main,
+    // F keys come in groups of four
+    Function(u8),
+    Navigation,
+    Numpad,
+}
+
+impl LayoutLearner {
+    pub fn new(path: PathBuf) -> LayoutLearner {
+        LayoutLearner {
+            path,
+            rows: Vec::new(),
+            row: Vec::new(),
+            message: None,
+            held_ctrl: None,
+        }
+    }
+
+    pub fn press(&mut self, key_event: &KeyEvent) -> Result<(), KbtError> {
+        self.message = None;
+
+        let Some(key) = key_event.key else {
+            self.message = Some(format!("kbt has no key for {}", key_event.raw_code_label()));
+            return Ok(());
+        };
+
+        // The ctrl chords that drive kbt reach the key backend too, they don't belong to the board
+        if let Some((ctrl, recorded)) = self.held_ctrl {
+            if recorded && self.row.last() == Some(&ctrl) {
+                self.row.pop();
+            }
+            self.held_ctrl = Some((ctrl, false));
+            return Ok(());
+        }
+
+        let recorded_row = self
+            .rows
+            .iter()
+            .chain(std::iter::once(&self.row))
+            .position(|row| row.contains(&key));
+        match recorded_row {
+            Some(row_idx) => {
+                self.message = Some(format!("{:?} is already on row {}", key, row_idx + 1));
+            }
+            None => self.row.push(key),
+        }
+        if matches!(key, Key::LeftCtrl | Key::RightCtrl) {
+            self.held_ctrl = Some((key, recorded_row.is_none()));
+        }
+
+        Ok(())
+    }
+
+    pub fn release(&mut self, key_event: &KeyEvent) {
+        if self
+            .held_ctrl
+            .is_some_and(|(ctrl, _)| key_event.key == Some(ctrl))
+        {
+            self.held_ctrl = None;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.rows = Vec::new();
+        self.row = Vec::new();
+        self.message = Some(String::from("starting over"));
+    }
+
+    // Every finished row is saved right away
+    pub fn end_row(&mut self) -> Result<(), KbtError> {
+        if !self.row.is_empty() {
+            self.rows.push(std::mem::take(&mut self.row));
+        }
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+
+        self.layout_file().save(&self.path)?;
+        self.message = Some(format!(
+            "row {} saved to {}",
+            self.rows.len(),
+            self.path.display()
+        ));
+
+        Ok(())
+    }
+
+    pub fn layout_file(&self) -> LayoutFile {
+        let name = self
+            .path
+            .file_stem()
+            .map_or(String::from("Learned layout"), |stem| {
+                stem.to_string_lossy().to_string()
+            });
+        let rows: Vec<&Vec<Key>> = self
+            .rows
+            .iter()
+            .chain(std::iter::once(&self.row).filter(|row| !row.is_empty()))
+            .collect();
+
+        LayoutFile {
+            name,
+            legends: Legends::Standard,
+            rows: size_rows(&rows),
+            unbound_keys: 0,
+        }
+    }
+
+    pub fn layout(&self) -> KeyboardLayout {
+        let layout_file = self.layout_file();
+        prepare_layout(
+            layout_file.name.clone(),
+            layout_file.key_rows(),
+            layout_file.legends,
+            layout_file.matrix(),
+        )
+    }
+}
+
+fn size_rows(rows: &[&Vec<Key>]) -> Vec<Vec<LayoutFileKey>> {
+    let standard_sizes = standard_sizes();
+    let mut sized_rows: Vec<Vec<LayoutFileKey>> = rows
+        .iter()
+        .map(|row| size_row(row, &standard_sizes))
+        .collect();
+
+    // Tall numpad keys reach into the next row, where they end up last
+    for row_idx in 0..sized_rows.len() {
+        let tall_keys: Vec<LayoutFileKey> = sized_rows[row_idx]
+            .iter()
+            .filter(|layout_key| matches!(layout_key.part, Some(VerticalKeyPart::Top)))
+            .cloned()
+            .collect();
+        for tall_key in tall_keys {
+            match sized_rows.get_mut(row_idx + 1) {
+                Some(next_row) => next_row.push(LayoutFileKey {
+                    part: Some(VerticalKeyPart::Bottom),
+                    ..tall_key
+                }),
+                None => {
+                    for layout_key in sized_rows[row_idx]
+                        .iter_mut()
+                        .filter(|layout_key| layout_key.key == tall_key.key)
+                    {
+                        layout_key.part = None;
+                    }
+                }
+            }
+        }
+    }
+
+    sized_rows
+}
+
+fn size_row(row: &[Key], standard_sizes: &HashMap<Key, KeyUI>) -> Vec<LayoutFileKey> {
+    let mut segments: Vec<(Cluster, Vec<LayoutFileKey>)> = Vec::new();
+    for key in row {
+        let cluster = cluster(*key);
+        let layout_key = match standard_sizes.get(key) {
+            Some(key_ui) => LayoutFileKey {
+                key: *key,
+                size: key_ui.size,
+                correction: key_ui.size_correction,
+                part: key_ui
+                    .vertical_key_part
+                    .filter(|part| matches!(part, VerticalKeyPart::Top)),
+                matrix: None,
+            },
+            None => LayoutFileKey {
+                key: *key,
+                size: KeySize::U1,
+                correction: None,
+                part: None,
+                matrix: None,
+            },
+        };
+
+        match segments.last_mut() {
+            Some((last_cluster, keys)) if *last_cluster == cluster => keys.push(layout_key),
+            _ => segments.push((cluster, vec![layout_key])),
+        }
+    }
+
+    let mut sized_row: Vec<LayoutFileKey> = Vec::new();
+    for (cluster, mut keys) in segments {
+        if cluster == Cluster::Main {
+            stretch_to_main_block(&mut keys);
+        }
+        if !sized_row.is_empty() {
+            sized_row.push(gap(KeySize::U05, Some(1)));
+        }
+        // A lone arrow up sits above arrow down
+        if cluster == Cluster::Navigation
+            && matches!(keys.as_slice(), [only_key] if only_key.key == Key::ArrowUp)
+        {
+            sized_row.push(gap(KeySize::U1, None));
+        }
+        sized_row.extend(keys);
+    }
+
+    sized_row
+}
+
+// The widest key of the block takes up what is missing to the standard row total, like the space
+// bar of a bottom row with fewer modifiers
+fn stretch_to_main_block(keys: &mut [LayoutFileKey]) {
+    let width = |layout_key: &LayoutFileKey| {
+        layout_key.size.static_len() as i16 + layout_key.correction.unwrap_or(0)
+    };
+    let total: i16 = keys.iter().map(width).sum();
+    let missing = MAIN_BLOCK_CELLS - total;
+
+    let Some(widest) = keys.iter_mut().max_by_key(|layout_key| width(layout_key)) else {
+        return;
+    };
+    let stretched = width(widest) + missing;
+    if missing == 0
+        || missing.abs() > MAX_ROW_STRETCH_CELLS
+        || width(widest) <= KeySize::U1.static_len() as i16
+        || stretched < KeySize::U1.static_len() as i16
+    {
+        return;
+    }
+
+    let (size, correction) = KeySize::fitting(stretched as u16);
+    widest.size = size;
+    widest.correction = correction;
+}
+
+fn gap(size: KeySize, correction: Option<i16>) -> LayoutFileKey {
+    LayoutFileKey {
+        key: Key::Separator,
+        size,
+        correction,
+        part: None,
+        matrix: None,
+    }
+}
+
+// First size each key has on the built-in layouts
+fn standard_sizes() -> HashMap<Key, KeyUI> {
+    let mut sizes: HashMap<Key, KeyUI> = HashMap::new();
+    let layouts: [&[&[KeyUI]]; 6] = [
+        &keyboard100::ROWS,
+        &keyboard80::ROWS,
+        &keyboard60::ROWS,
+        &keyboard_jis::ROWS,
+        &keyboard_abnt2::ROWS,
+        &keyboard_korean::ROWS,
+    ];
+    for key_ui in layouts
+        .iter()
+        .flat_map(|rows| rows.iter())
+        .flat_map(|row| row.iter())
+    {
+        if key_ui.key != Key::Separator {
+            sizes.entry(key_ui.key).or_insert(*key_ui);
+        }
+    }
+
+    sizes
+}
+
+fn cluster(key: Key) -> Cluster {
+    match key {
+        Key::F1 | Key::F2 | Key::F3 | Key::F4 => Cluster::Function(0),
+        Key::F5 | Key::F6 | Key::F7 | Key::F8 => Cluster::Function(1),
+        Key::F9 | Key::F10 | Key::F11 | Key::F12 => Cluster::Function(2),
+        Key::F13 | Key::F14 | Key::F15 | Key::F16 => Cluster::Function(3),
+        Key::F17 | Key::F18 | Key::F19 | Key::F20 => Cluster::Function(4),
+        Key::F21 | Key::F22 | Key::F23 | Key::F24 => Cluster::Function(5),
+        Key::PrintScreen
+        | Key::ScrollLock
+        | Key::PauseBreak
+        | Key::Insert
+        | Key::Home
+        | Key::PgUp
+        | Key::Delete
+        | Key::End
+        | Key::PgDown
+        | Key::ArrowUp
+        | Key::ArrowDown
+        | Key::ArrowLeft
+        | Key::ArrowRight => Cluster::Navigation,
+        Key::NumLock
+        | Key::Div
+        | Key::Mul
+        | Key::Minus
+        | Key::Plus
+        | Key::Decimal
+        | Key::NumpadEnter
+        | Key::NumpadComma
+        | Key::NumpadZero
+        | Key::NumpadOne
+        | Key::NumpadTwo
+        | Key::NumpadThree
+        | Key::NumpadFour
+        | Key::NumpadFive
+        | Key::NumpadSix
+        | Key::NumpadSeven
+        | Key::NumpadEight
+        | Key::NumpadNine => Cluster::Numpad,
+        _ => Cluster::Main,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn width(layout_key: &LayoutFileKey) -> i16 {
+        layout_key.size.static_len() as i16 + layout_key.correction.unwrap_or(0)
+    }
+
+    fn standard_width(key: Key) -> i16 {
+        let key_ui = standard_sizes()[&key];
+        key_ui.size.static_len() as i16 + key_ui.size_correction.unwrap_or(0)
+    }
+
+    #[test]
+    fn stretches_the_space_bar_of_a_short_bottom_row() {
+        let row = vec![
+            Key::LeftCtrl,
+            Key::LeftSuper,
+            Key::LeftAlt,
+            Key::Spacebar,
+            Key::RightAlt,
+            Key::RightCtrl,
+        ];
+        let sized_row = size_row(&row, &standard_sizes());
+
+        assert_eq!(sized_row.iter().map(width).sum::<i16>(), MAIN_BLOCK_CELLS);
+        assert!(width(&sized_row[3]) > standard_width(Key::Spacebar));
+        for layout_key in sized_row.iter().filter(|k| k.key != Key::Spacebar) {
+            assert_eq!(width(layout_key), standard_width(layout_key.key));
+        }
+    }
+
+    #[test]
+    fn splits_tall_numpad_keys_over_two_rows() {
+        let top = vec![
+            Key::NumpadSeven,
+            Key::NumpadEight,
+            Key::NumpadNine,
+            Key::Plus,
+        ];
+        let bottom = vec![Key::NumpadFour, Key::NumpadFive, Key::NumpadSix];
+        let sized_rows = size_rows(&[&top, &bottom]);
+
+        let keys = |row: &[LayoutFileKey]| -> Vec<(Key, Option<VerticalKeyPart>)> {
+            row.iter().map(|k| (k.key, k.part)).collect()
+        };
+        assert!(
+            keys(&sized_rows[0])
+                == [
+                    (Key::NumpadSeven, None),
+                    (Key::NumpadEight, None),
+                    (Key::NumpadNine, None),
+                    (Key::Plus, Some(VerticalKeyPart::Top)),
+                ]
+        );
+        assert!(
+            keys(&sized_rows[1])
+                == [
+                    (Key::NumpadFour, None),
+                    (Key::NumpadFive, None),
+                    (Key::NumpadSix, None),
+                    (Key::Plus, Some(VerticalKeyPart::Bottom)),
+                ]
+        );
+
+        // Without a row below the key is drawn one row tall
+        let sized_rows = size_rows(&[&top]);
+        assert!(sized_rows[0].iter().all(|k| k.part.is_none()));
+    }
+
+    #[test]
+    fn leaves_short_rows_alone_and_puts_arrow_up_above_arrow_down() {
+        let row = vec![Key::RightShift, Key::ArrowUp];
+        let sized_row = size_row(&row, &standard_sizes());
+
+        let keys: Vec<Key> = sized_row.iter().map(|k| k.key).collect();
+        assert_eq!(
+            keys,
+            [
+                Key::RightShift,
+                Key::Separator,
+                Key::Separator,
+                Key::ArrowUp
+            ]
+        );
+        // The shift is far off the block width, so it isn't stretched
+        assert_eq!(width(&sized_row[0]), standard_width(Key::RightShift));
+        assert_eq!(width(&sized_row[2]), KeySize::U1.static_len() as i16);
+    }
+}
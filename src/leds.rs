@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+#[cfg(target_os = "linux")]
+use crate::evdev_backend::EvdevLeds;
+use crate::key::Key;
+
+// The OS flips the LED after it handled the lock key press, give it a moment before failing the check
+const LED_SETTLE: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum LockKey {
+    Caps,
+    Num,
+    Scroll,
+}
+
+impl LockKey {
+    pub const ALL: [LockKey; 3] = [LockKey::Caps, LockKey::Num, LockKey::Scroll];
+
+    pub fn from_key(key: Key) -> Option<LockKey> {
+        match key {
+            Key::CapsLock => Some(LockKey::Caps),
+            Key::NumLock => Some(LockKey::Num),
+            Key::ScrollLock => Some(LockKey::Scroll),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LockKey::Caps => "Caps",
+            LockKey::Num => "Num",
+            LockKey::Scroll => "Scroll",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct LockLeds {
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+impl LockLeds {
+    pub fn is_on(&self, lock_key: LockKey) -> bool {
+        match lock_key {
+            LockKey::Caps => self.caps_lock,
+            LockKey::Num => self.num_lock,
+            LockKey::Scroll => self.scroll_lock,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LedCheck {
+    Flipped,
+    DidNotFlip,
+}
+
+// Every lock key press is expected to flip its LED
+#[derive(Default)]
+pub struct LedTest {
+    pending: Vec<(LockKey, bool, Instant)>,
+    pub results: HashMap<LockKey, LedCheck>,
+}
+
+impl LedTest {
+    pub fn lock_pressed(&mut self, lock_key: LockKey, leds_before: &LockLeds, at: Instant) {
+        self.pending
+            .retain(|(pending_key, _, _)| *pending_key != lock_key);
+        self.pending
+            .push((lock_key, leds_before.is_on(lock_key), at));
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn resolve(&mut self, leds_now: &LockLeds, now: Instant) {
+        let results = &mut self.results;
+        self.pending.retain(|(lock_key, was_on, pressed_at)| {
+            if leds_now.is_on(*lock_key) != *was_on {
+                results.insert(*lock_key, LedCheck::Flipped);
+                false
+            } else if now.saturating_duration_since(*pressed_at) > LED_SETTLE {
+                results.insert(*lock_key, LedCheck::DidNotFlip);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+// Where the LED state is read from, opened once per session
+pub enum LedSource {
+    // Without an X server there is nothing to ask
+    Unavailable,
+    X,
+    #[cfg(target_os = "linux")]
+    Evdev(EvdevLeds),
+}
+
+impl LedSource {
+    pub fn read(&self) -> Option<LockLeds> {
+        match self {
+            LedSource::Unavailable => None,
+            LedSource::X => query_x_leds(),
+            #[cfg(target_os = "linux")]
+            LedSource::Evdev(leds) => leds
+                .read()
+                .inspect_err(|err| log::error!("Failed to read LEDs: {}", err.message))
+                .ok(),
+        }
+    }
+}
+
+// LED state as the X server sees it, for backends that can't read devices
+fn query_x_leds() -> Option<LockLeds> {
+    let output = Command::new("xset").arg("q").output().ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+
+    let led = |name: &str| {
+        output
+            .split_once(name)
+            .and_then(|(_, rest)| rest.split_whitespace().next())
+            .map(|value| value == "on")
+    };
+
+    Some(LockLeds {
+        caps_lock: led("Caps Lock:")?,
+        num_lock: led("Num Lock:")?,
+        scroll_lock: led("Scroll Lock:")?,
+    })
+}
@@ -0,0 +1,189 @@
+use std::{env, process::Command};
+
+use clap::ValueEnum;
+
+use crate::{key::Key, model::Legends};
+
+// Keys are still identified by their physical (US) position, a locale only changes what is printed on them.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Locale {
+    Us,
+    Fr,
+    De,
+    Dvorak,
+    Colemak,
+}
+
+impl Locale {
+    pub fn detect() -> Option<Locale> {
+        let (layout, variant) = match env::var("XKB_DEFAULT_LAYOUT") {
+            Ok(layout) => (layout, env::var("XKB_DEFAULT_VARIANT").unwrap_or_default()),
+            Err(_) => query_x_keymap()?,
+        };
+
+        let layout = layout.split(',').next().unwrap_or_default().trim();
+        let variant = variant.split(',').next().unwrap_or_default().trim();
+
+        match (layout, variant) {
+            ("fr", _) => Some(Locale::Fr),
+            ("de", _) => Some(Locale::De),
+            (_, "dvorak") | ("dvorak", _) => Some(Locale::Dvorak),
+            (_, "colemak") | ("colemak", _) => Some(Locale::Colemak),
+            ("us", _) => Some(Locale::Us),
+            _ => None,
+        }
+    }
+
+    pub fn legend(&self, key: Key) -> Option<&'static str> {
+        match self {
+            Locale::Us => None,
+            Locale::Fr => azerty_legend(key),
+            Locale::De => qwertz_legend(key),
+            Locale::Dvorak => dvorak_legend(key),
+            Locale::Colemak => colemak_legend(key),
+        }
+    }
+}
+
+// What is printed on a key everywhere kbt draws one, the locale wins over the layout's legends
+pub fn legend(key: Key, legends: Legends, locale: Option<Locale>) -> String {
+    locale
+        .and_then(|locale| locale.legend(key))
+        .map_or_else(|| key.legend(legends), String::from)
+}
+
+fn query_x_keymap() -> Option<(String, String)> {
+    let output = Command::new("setxkbmap").arg("-query").output().ok()?;
+    let output = String::from_utf8(output.stdout).ok()?;
+
+    let field = |name: &str| {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .map(|value| value.trim().to_string())
+    };
+
+    Some((field("layout:")?, field("variant:").unwrap_or_default()))
+}
+
+fn azerty_legend(key: Key) -> Option<&'static str> {
+    match key {
+        Key::Grave => Some("²"),
+        Key::One => Some("1&"),
+        Key::Two => Some("2é"),
+        Key::Three => Some("3\""),
+        Key::Four => Some("4'"),
+        Key::Five => Some("5("),
+        Key::Six => Some("6-"),
+        Key::Seven => Some("7è"),
+        Key::Eight => Some("8_"),
+        Key::Nine => Some("9ç"),
+        Key::Zero => Some("0à"),
+        Key::Hyphen => Some("°)"),
+        Key::Equal => Some("+="),
+        Key::Q => Some("A"),
+        Key::W => Some("Z"),
+        Key::LeftBracket => Some("¨^"),
+        Key::RightBracket => Some("£$"),
+        Key::A => Some("Q"),
+        Key::SemiColon => Some("M"),
+        Key::Apostrophe => Some("%ù"),
+        Key::Backslash => Some("µ*"),
+        Key::Z => Some("W"),
+        Key::M => Some("?,"),
+        Key::Comma => Some(".;"),
+        Key::Period => Some("/:"),
+        Key::QuestionMark => Some("§!"),
+        _ => None,
+    }
+}
+
+fn qwertz_legend(key: Key) -> Option<&'static str> {
+    match key {
+        Key::Grave => Some("°^"),
+        Key::Two => Some("\"2"),
+        Key::Three => Some("§3"),
+        Key::Six => Some("&6"),
+        Key::Seven => Some("/7"),
+        Key::Eight => Some("(8"),
+        Key::Nine => Some(")9"),
+        Key::Zero => Some("=0"),
+        Key::Hyphen => Some("?ß"),
+        Key::Equal => Some("`´"),
+        Key::Y => Some("Z"),
+        Key::LeftBracket => Some("Ü"),
+        Key::RightBracket => Some("*+"),
+        Key::SemiColon => Some("Ö"),
+        Key::Apostrophe => Some("Ä"),
+        Key::Backslash => Some("'#"),
+        Key::IntlBackslash => Some("><"),
+        Key::Z => Some("Y"),
+        Key::Comma => Some(";,"),
+        Key::Period => Some(":."),
+        Key::QuestionMark => Some("_-"),
+        _ => None,
+    }
+}
+
+fn dvorak_legend(key: Key) -> Option<&'static str> {
+    match key {
+        Key::Hyphen => Some("{["),
+        Key::Equal => Some("}]"),
+        Key::Q => Some("\"'"),
+        Key::W => Some("<,"),
+        Key::E => Some(">."),
+        Key::R => Some("P"),
+        Key::T => Some("Y"),
+        Key::Y => Some("F"),
+        Key::U => Some("G"),
+        Key::I => Some("C"),
+        Key::O => Some("R"),
+        Key::P => Some("L"),
+        Key::LeftBracket => Some("?/"),
+        Key::RightBracket => Some("+="),
+        Key::S => Some("O"),
+        Key::D => Some("E"),
+        Key::F => Some("U"),
+        Key::G => Some("I"),
+        Key::H => Some("D"),
+        Key::J => Some("H"),
+        Key::K => Some("T"),
+        Key::L => Some("N"),
+        Key::SemiColon => Some("S"),
+        Key::Apostrophe => Some("_-"),
+        Key::Z => Some(":;"),
+        Key::X => Some("Q"),
+        Key::C => Some("J"),
+        Key::V => Some("K"),
+        Key::B => Some("X"),
+        Key::N => Some("B"),
+        Key::Comma => Some("W"),
+        Key::Period => Some("V"),
+        Key::QuestionMark => Some("Z"),
+        _ => None,
+    }
+}
+
+fn colemak_legend(key: Key) -> Option<&'static str> {
+    match key {
+        Key::E => Some("F"),
+        Key::R => Some("P"),
+        Key::T => Some("G"),
+        Key::Y => Some("J"),
+        Key::U => Some("L"),
+        Key::I => Some("U"),
+        Key::O => Some("Y"),
+        Key::P => Some(":;"),
+        Key::S => Some("R"),
+        Key::D => Some("S"),
+        Key::F => Some("T"),
+        Key::G => Some("D"),
+        Key::J => Some("N"),
+        Key::K => Some("E"),
+        Key::L => Some("I"),
+        Key::SemiColon => Some("O"),
+        Key::N => Some("K"),
+        Key::CapsLock => Some("Back"),
+        _ => None,
+    }
+}
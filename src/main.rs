@@ -1,110 +1,806 @@
+mod batch;
+#[cfg(unix)]
+mod control_socket;
+mod editor;
+#[cfg(target_os = "linux")]
+mod evdev_backend;
+mod event_stream;
 mod generic_backend;
+mod ghosting;
+mod history;
+mod html_report;
 mod key;
 mod keyboard100;
 mod keyboard60;
 mod keyboard80;
+mod keyboard_abnt2;
+mod keyboard_apple_ansi;
+mod keyboard_apple_iso;
+mod keyboard_extra_rows;
+mod keyboard_jis;
+mod keyboard_korean;
+mod layout_file;
+mod learn_layout;
+mod leds;
+mod locale;
 mod menu;
 mod model;
+mod modifier_matrix;
+mod physical_layout;
+mod polling;
+mod qmk;
+mod report;
+mod svg;
+mod via;
 mod view;
 
 use std::{
-    collections::HashMap,
-    io,
-    sync::mpsc::{channel, Receiver, Sender},
+    collections::{HashMap, HashSet},
+    env,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use batch::Batch;
+#[cfg(unix)]
+use control_socket::{ControlSocket, SessionKind};
 use crossterm::{
     event::{self, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use generic_backend::GenericKeyBackend;
+use editor::LayoutEditor;
+use event_stream::{EventStream, Snapshot, StreamEvent, StreamTarget};
+use generic_backend::{GenericKeyBackend, KeyStreamGuard};
+use ghosting::MatrixPosition;
+use key::Key;
+use layout_file::LayoutFile;
+use learn_layout::LayoutLearner;
+use leds::{LedSource, LedTest};
+use locale::Locale;
+use log::LevelFilter;
 use model::*;
+use modifier_matrix::ModifierMatrix;
+use polling::PollingStats;
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     layout::Rect,
     Terminal,
 };
+use report::Report;
 use view::draw_too_small;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 #[command(version)]
-struct Args {}
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Where key events are read from. `evdev` reads /dev/input directly and is Linux only
+    #[arg(long, value_enum, default_value_t = KeyBackend::Generic)]
+    backend: KeyBackend,
+
+    /// Use this built-in layout instead of picking one in the menu
+    #[arg(long, value_enum, conflicts_with_all = ["layout_file", "qmk_info", "via"])]
+    layout: Option<KeyboardSize>,
+
+    /// Load the layout from a JSON layout file instead of picking a built-in one
+    #[arg(long)]
+    layout_file: Option<PathBuf>,
+
+    /// Import the layout from a QMK info.json or keyboard.json
+    #[arg(long, conflicts_with = "layout_file")]
+    qmk_info: Option<PathBuf>,
+
+    /// Layout macro to import from --qmk-info, e.g. LAYOUT_ansi. Defaults to the keymap's or the only one
+    #[arg(long, requires = "qmk_info")]
+    qmk_layout: Option<String>,
+
+    /// QMK keymap.json whose base layer binds the imported keys, the `label`s of info.json are used otherwise
+    #[arg(long, requires = "qmk_info")]
+    qmk_keymap: Option<PathBuf>,
+
+    /// Load the layout from a VIA or Vial keyboard definition JSON
+    #[arg(long, conflicts_with_all = ["layout_file", "qmk_info"])]
+    via: Option<PathBuf>,
+
+    /// VIA layout option as NAME=CHOICE, e.g. "Split Backspace=on" or "Bottom Row=WKL", can be repeated
+    #[arg(long, requires = "via")]
+    via_option: Vec<String>,
+
+    /// Saved VIA keymap or Vial .vil whose base layer binds the keys, they are guessed from their position otherwise
+    #[arg(long, requires = "via")]
+    via_keymap: Option<PathBuf>,
+
+    /// What to test, `modifiers` walks through every modifier x alpha key combination, `matrix` shows
+    /// held keys on the switch matrix of a --layout-file, --qmk-info or --via
+    #[arg(long, value_enum, default_value_t = TestMode::Keys)]
+    mode: TestMode,
+
+    /// Only count events from this input device, e.g. /dev/input/event3. Needs the evdev backend
+    #[arg(long)]
+    device: Option<PathBuf>,
+
+    /// Test a second keyboard side by side with the one from --device, e.g. a known-good reference board
+    #[arg(long, requires = "device")]
+    compare: Option<PathBuf>,
+
+    /// Flag keys held down longer than this many milliseconds as stuck
+    #[arg(long, default_value_t = 2000)]
+    stuck_threshold_ms: u64,
+
+    /// Write the app log, including every key event with its raw code, to this file
+    #[arg(long)]
+    log: Option<PathBuf>,
+
+    /// Write a JSON session report to this file on exit
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Write a self-contained HTML report on exit, hover a key for its presses, hold times, chatter and raw codes
+    #[arg(long)]
+    html_report: Option<PathBuf>,
+
+    /// Append a summary of the session to this JSON-lines history file, also read by `history` and `compare`
+    #[arg(long)]
+    history: Option<PathBuf>,
+
+    /// Serial number of the tested board, kept in the report and the history
+    #[arg(long)]
+    serial: Option<String>,
+
+    /// Test boards one after another: type or scan each serial, a board is done once every key was
+    /// pressed and released (ctrl+n ends it early), reports get the serial added to their file name
+    #[arg(long, conflicts_with = "serial")]
+    batch: bool,
+
+    /// Write an SVG picture of the board with untested, tested, chattering and stuck keys on exit
+    #[arg(long)]
+    svg: Option<PathBuf>,
+
+    /// Publish key events, key state changes and test milestones as JSON lines. `-` writes them to
+    /// stdout and draws the board on stderr, anything else is a Unix socket to create and serve them on
+    #[arg(long, value_name = "-|SOCKET")]
+    emit_json: Option<StreamTarget>,
+
+    /// Accept commands on this Unix socket, one per line: reset, select-layout 80, start-test [serial],
+    /// finish, export-report <path> and quit
+    #[arg(long)]
+    control_socket: Option<PathBuf>,
+
+    /// Add a row above the layout, can be repeated. Keys on these rows need the evdev backend
+    #[arg(long, value_enum)]
+    extra_row: Vec<ExtraRow>,
+
+    /// Relabel keys for a locale, detected from XKB_DEFAULT_LAYOUT or the X keymap when omitted
+    #[arg(long, value_enum)]
+    legends: Option<Locale>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Edit a layout file, keys are assigned by pressing them
+    EditLayout {
+        /// Layout file to edit. When missing, it is created from --layout-file, --qmk-info, --via or a built-in layout
+        path: PathBuf,
+    },
+    /// Draw a layout from --layout-file, --qmk-info, --via or the built-in ones as an SVG picture
+    ExportSvg {
+        /// SVG file to write
+        path: PathBuf,
+    },
+    /// List the sessions of the --history file
+    History,
+    /// Show keys that degraded between two sessions of the --history file, e.g. chatter or hold time spread
+    Compare {
+        /// Session id like #3, or a serial for the latest session of that board
+        before: String,
+        /// Session id like #3, or a serial for the latest session of that board
+        after: String,
+    },
+    /// Build a layout file by pressing every key of the board, row by row
+    LearnLayout {
+        /// Layout file to write, saved after every row
+        path: PathBuf,
+    },
+}
 
 pub const KEY_HEIGHT: u16 = 3;
 
+// How often held keys are checked when no events arrive
+const STUCK_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
 fn main() -> Result<(), KbtError> {
-    // simple_logging::log_to_file("kbt.log", LevelFilter::Info)?;
+    let args = Args::parse();
+    if let Some(path) = &args.log {
+        simple_logging::log_to_file(path, LevelFilter::Info)?;
+    }
     log::info!("start the app!");
-    let _ = Args::parse();
 
-    run().map(|_| println!("bye!"))
+    // The event stream owns stdout until the end
+    let quiet = matches!(args.emit_json, Some(StreamTarget::Stdout));
+    run(args).map(|_| {
+        if !quiet {
+            println!("bye!")
+        }
+    })
 }
 
-fn run() -> Result<(), KbtError> {
-    let mut stdout = io::stdout();
+fn run(args: Args) -> Result<(), KbtError> {
+    match &args.command {
+        Some(Command::History) => return history::list(history_path(&args)?),
+        Some(Command::Compare { before, after }) => {
+            return history::compare(history_path(&args)?, before, after)
+        }
+        _ => {}
+    }
+
+    let layout_file = match &args.command {
+        Some(Command::EditLayout { path }) if path.exists() => Some(LayoutFile::load(path)?),
+        _ => import_layout_file(&args)?,
+    };
+    // Previews of imported layouts don't need the terminal
+    let layout_file = match (&args.command, layout_file) {
+        (Some(Command::ExportSvg { path }), Some(layout_file)) => {
+            let layout = load_layout_file(layout_file, &args.extra_row);
+            return svg::write(path, &layout, legends_locale(&args), None);
+        }
+        (_, layout_file) => layout_file,
+    };
+    if let (None, TestMode::Matrix, false) = (
+        &args.command,
+        args.mode,
+        layout_file
+            .as_ref()
+            .is_some_and(|layout_file| !layout_file.matrix().is_empty()),
+    ) {
+        return Err(KbtError {
+            message: String::from(
+                "Matrix mode needs a --layout-file, --qmk-info or --via with matrix positions",
+            ),
+        });
+    }
+
+    if args.batch && !matches!(args.mode, TestMode::Keys) {
+        return Err(KbtError {
+            message: String::from("Batch mode runs the key test, it can't be combined with --mode"),
+        });
+    }
+
+    let mut output: Box<dyn Write> = match &args.emit_json {
+        Some(StreamTarget::Stdout) => Box::new(io::stderr()),
+        _ => Box::new(io::stdout()),
+    };
 
-    execute!(stdout, EnterAlternateScreen)?;
+    execute!(output, EnterAlternateScreen)?;
     enable_raw_mode()?;
 
-    let backend = CrosstermBackend::new(stdout);
+    let backend = CrosstermBackend::new(output);
     let mut terminal = Terminal::new(backend)?;
 
-    let menu_result: MenuResult = menu::run_menu(&mut terminal)?;
+    match &args.command {
+        Some(Command::EditLayout { path }) => edit_layout(&mut terminal, &args, layout_file, path),
+        Some(Command::LearnLayout { path }) => learn_layout(&mut terminal, &args, path),
+        Some(Command::ExportSvg { path }) => export_svg(&mut terminal, &args, path),
+        None => test_keyboard(&mut terminal, &args, layout_file),
+        Some(Command::History | Command::Compare { .. }) => {
+            unreachable!("handled before the terminal is set up")
+        }
+    }?;
+
+    // restore terminal
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    Ok(())
+}
+
+fn history_path(args: &Args) -> Result<&Path, KbtError> {
+    args.history.as_deref().ok_or(KbtError {
+        message: String::from("Pass the history file with --history <PATH>"),
+    })
+}
+
+fn import_layout_file(args: &Args) -> Result<Option<LayoutFile>, KbtError> {
+    match (&args.layout_file, &args.qmk_info, &args.via) {
+        (Some(path), _, _) => LayoutFile::load(path).map(Some),
+        (None, Some(path), _) => {
+            qmk::import(path, args.qmk_layout.as_deref(), args.qmk_keymap.as_deref()).map(Some)
+        }
+        (None, None, Some(path)) => {
+            via::import(path, &args.via_option, args.via_keymap.as_deref()).map(Some)
+        }
+        (None, None, None) => Ok(None),
+    }
+}
+
+fn test_keyboard<B: Backend>(
+    terminal: &mut Terminal<B>,
+    args: &Args,
+    layout_file: Option<LayoutFile>,
+) -> Result<(), KbtError> {
+    let menu_result = match layout_file {
+        Some(layout_file) => MenuResult::Selected(load_layout_file(layout_file, &args.extra_row)),
+        None => {
+            select_layout(terminal, args)?.map(|selection| load_layout(&selection, &args.extra_row))
+        }
+    };
 
     match menu_result {
         MenuResult::Terminate => Ok(()),
-        MenuResult::KeyboardSelected(selection) => {
-            let (sender, receiver): (Sender<AppEvent>, Receiver<AppEvent>) = channel();
-            let (_up_guard, _down_guard) = GenericKeyBackend::subscribe(&sender);
-            let handle = thread::spawn(move || listen_for_control(sender));
-            let layout = match &selection {
-                    KeyboardSize::Keyboard60 => {
-                        prepare_layout(keyboard60::ROWS.map(|rows| rows.to_vec()).to_vec())
-                    }
-                    KeyboardSize::Keyboard80 => {
-                        prepare_layout(keyboard80::ROWS.map(|rows| rows.to_vec()).to_vec())
-                    }
-                    KeyboardSize::Keyboard100 => {
-                        prepare_layout(keyboard100::ROWS.map(|rows| rows.to_vec()).to_vec())
-                    }
-                };
+        MenuResult::Selected(layout) => match select_device(terminal, args)? {
+            MenuResult::Terminate => Ok(()),
+            MenuResult::Selected(device) => {
+                let compare = select_compare_device(args)?;
+                run_session(terminal, args, layout, device, compare)
+            }
+        },
+    }
+}
+
+fn select_layout<B: Backend>(
+    terminal: &mut Terminal<B>,
+    args: &Args,
+) -> Result<MenuResult<KeyboardSize>, KbtError> {
+    match &args.layout {
+        Some(selection) => Ok(MenuResult::Selected(selection.clone())),
+        None => menu::run_menu(terminal),
+    }
+}
+
+fn edit_layout<B: Backend>(
+    terminal: &mut Terminal<B>,
+    args: &Args,
+    layout_file: Option<LayoutFile>,
+    path: &Path,
+) -> Result<(), KbtError> {
+    let menu_result = match layout_file {
+        Some(layout_file) => MenuResult::Selected(layout_file),
+        None => select_layout(terminal, args)?
+            .map(|selection| LayoutFile::from_layout(&load_layout(&selection, &args.extra_row))),
+    };
+    let MenuResult::Selected(layout_file) = menu_result else {
+        return Ok(());
+    };
+
+    let mut editor = LayoutEditor::new(layout_file, path.to_path_buf());
+    let locale = legends_locale(args);
+    with_key_events(args.backend, |receiver| {
+        run_editor(terminal, receiver, &mut editor, locale)
+    })
+}
+
+fn export_svg<B: Backend>(
+    terminal: &mut Terminal<B>,
+    args: &Args,
+    path: &Path,
+) -> Result<(), KbtError> {
+    match select_layout(terminal, args)? {
+        MenuResult::Terminate => Ok(()),
+        MenuResult::Selected(selection) => {
+            let layout = load_layout(&selection, &args.extra_row);
+            svg::write(path, &layout, legends_locale(args), None)
+        }
+    }
+}
+
+fn learn_layout<B: Backend>(
+    terminal: &mut Terminal<B>,
+    args: &Args,
+    path: &Path,
+) -> Result<(), KbtError> {
+    let mut learner = LayoutLearner::new(path.to_path_buf());
+    let locale = legends_locale(args);
+    with_key_events(args.backend, |receiver| {
+        run_learner(terminal, receiver, &mut learner, locale)
+    })
+}
+
+// --legends, or the XKB layout of the session
+fn legends_locale(args: &Args) -> Option<Locale> {
+    args.legends.or_else(Locale::detect)
+}
+
+// Key events from the backend plus control events from the terminal, until ctrl+q
+fn with_key_events(
+    backend: KeyBackend,
+    run: impl FnOnce(&Receiver<AppEvent>) -> Result<(), KbtError>,
+) -> Result<(), KbtError> {
+    let (sender, receiver): (Sender<AppEvent>, Receiver<AppEvent>) = channel();
+    let _guards = subscribe_key_backend(backend, &sender)?;
+    let handle = thread::spawn(move || listen_for_control(sender, ControlKeys::CtrlChords));
+
+    let res = run(&receiver);
+
+    match handle.join() {
+        Ok(_) => res,
+        Err(_) => Err(KbtError {
+            message: String::from("Control listener thread failed to exit"),
+        }),
+    }
+}
+
+fn run_session<B: Backend>(
+    terminal: &mut Terminal<B>,
+    args: &Args,
+    layout: KeyboardLayout,
+    device: Option<InputDevice>,
+    compare: Option<InputDevice>,
+) -> Result<(), KbtError> {
+    let (sender, receiver): (Sender<AppEvent>, Receiver<AppEvent>) = channel();
+    let _guards = subscribe_key_backend(args.backend, &sender)?;
+    let _control_socket = serve_control_socket(args, &sender)?;
+    let control_keys = match args.mode {
+        TestMode::Modifiers => ControlKeys::EscHold,
+        TestMode::Keys | TestMode::Matrix => ControlKeys::CtrlChords,
+    };
+    let handle = thread::spawn(move || listen_for_control(sender, control_keys));
+
+    let led_source = open_led_source(args.backend, device.as_ref());
+    let modifier_matrix = match args.mode {
+        TestMode::Keys | TestMode::Matrix => None,
+        TestMode::Modifiers => Some(ModifierMatrix::new(&layout)),
+    };
+
+    let mut app = App {
+        key_states: HashMap::new(),
+        event_receiver: receiver,
+        layout,
+        locale: legends_locale(args),
+        last_key_event: None,
+        unmapped_key_states: HashMap::new(),
+        device,
+        key_timings: KeyTimings::default(),
+        polling: PollingStats::default(),
+        backend: args.backend,
+        stuck_threshold: Duration::from_millis(args.stuck_threshold_ms),
+        stuck_keys: HashMap::new(),
+        lost_releases: HashSet::new(),
+        leds: led_source.read(),
+        led_source,
+        led_test: LedTest::default(),
+        modifier_matrix,
+        ghost_keys: HashSet::new(),
+        mode: args.mode,
+        compare: compare.map(CompareBoard::new),
+        raw_codes: HashMap::new(),
+        started_at: Instant::now(),
+        serial: args.serial.clone(),
+        batch: args.batch.then(Batch::new),
+        event_stream: args.emit_json.as_ref().map(EventStream::open).transpose()?,
+    };
+
+    if let Some(stream) = &app.event_stream {
+        stream.emit(&StreamEvent::SessionStarted {
+            layout: &app.layout.name,
+            device: app.device.as_ref().map(|device| device.to_string()),
+            serial: app.serial.as_deref(),
+            keys: app.layout.keys().len(),
+        });
+    }
+
+    let res = if args.batch {
+        run_batch(terminal, args, &mut app)
+    } else {
+        run_keyboard(terminal, &mut app).and_then(|_| write_reports(args, &app))
+    };
 
-            let initial_app = App {
-                key_states: HashMap::new(),
-                event_receiver: receiver,
-                layout,
+    if let (Ok(_), Some(stream)) = (&res, &app.event_stream) {
+        stream.emit(&StreamEvent::SessionEnded {
+            report: Box::new(Report::new(&app)),
+        });
+    }
+
+    // After a quit from the control socket the terminal listener still waits for a key, it ends with the process
+    if !handle.is_finished() {
+        return res;
+    }
+    match handle.join() {
+        Ok(_) => res,
+        Err(_) => Err(KbtError {
+            message: String::from("Control listener thread failed to exit"),
+        }),
+    }
+}
+
+#[cfg(unix)]
+fn serve_control_socket(
+    args: &Args,
+    sender: &Sender<AppEvent>,
+) -> Result<Option<ControlSocket>, KbtError> {
+    args.control_socket
+        .as_ref()
+        .map(|path| {
+            let session = SessionKind {
+                extra_rows: args.extra_row.clone(),
+                batch: args.batch,
+                mode: args.mode,
             };
+            ControlSocket::serve(path, sender.clone(), session)
+        })
+        .transpose()
+}
+
+#[cfg(not(unix))]
+fn serve_control_socket(args: &Args, _sender: &Sender<AppEvent>) -> Result<Option<()>, KbtError> {
+    match args.control_socket {
+        Some(_) => Err(KbtError {
+            message: String::from("The control socket needs a Unix system"),
+        }),
+        None => Ok(None),
+    }
+}
+
+fn write_reports(args: &Args, state: &App) -> Result<(), KbtError> {
+    let path = |path: &PathBuf| match (&state.batch, &state.serial) {
+        (Some(_), Some(serial)) => batch::unit_path(path, serial),
+        _ => Ok(path.clone()),
+    };
+
+    if let Some(report_path) = &args.report {
+        Report::new(state).write(&path(report_path)?)?;
+    }
+    if let Some(html_path) = &args.html_report {
+        html_report::write(&path(html_path)?, state)?;
+    }
+    if let Some(svg_path) = &args.svg {
+        svg::write(
+            &path(svg_path)?,
+            &state.layout,
+            state.locale,
+            Some(&svg::key_marks(state)),
+        )?;
+    }
+    if let Some(history_path) = &args.history {
+        history::append(history_path, state)?;
+    }
+
+    Ok(())
+}
 
-            let res = run_keyboard(&mut terminal, initial_app);
+// Serial prompt and key test in turns, until ctrl+q. A unit quit halfway through is not recorded
+fn run_batch<B: Backend>(
+    terminal: &mut Terminal<B>,
+    args: &Args,
+    state: &mut App,
+) -> Result<(), KbtError> {
+    loop {
+        let Some(serial) = read_serial(terminal, state)? else {
+            return Ok(());
+        };
+        state.reset();
+        state.serial = Some(serial.clone());
+        if let Some(stream) = &state.event_stream {
+            stream.emit(&StreamEvent::UnitStarted { serial: &serial });
+        }
 
-            match handle.join() {
-                Ok(_) => res,
-                Err(_) => Err(KbtError {
-                    message: String::from("Control listener thread failed to exit"),
-                }),
+        if let SessionEnd::Terminated = run_keyboard(terminal, state)? {
+            return Ok(());
+        }
+        write_reports(args, state)?;
+
+        let report = Report::new(state);
+        if let Some(batch) = &mut state.batch {
+            batch.record(serial, &report);
+        }
+        if let (Some(stream), Some(batch)) = (&state.event_stream, &state.batch) {
+            if let Some(unit) = &batch.last_unit {
+                stream.emit(&StreamEvent::UnitFinished {
+                    serial: &unit.serial,
+                    passed: unit.problems.is_empty(),
+                    problems: &unit.problems,
+                });
             }
         }
-    }?;
+    }
+}
 
-    // restore terminal
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
+// Key presses from any device make up the serial, a barcode reader is usually a keyboard of its own
+fn read_serial<B: Backend>(
+    terminal: &mut Terminal<B>,
+    state: &mut App,
+) -> Result<Option<String>, KbtError> {
+    loop {
+        let Some(batch) = &mut state.batch else {
+            return Ok(None);
+        };
+        if view::serial_prompt_fits(terminal.size()?, batch) {
+            terminal.draw(|f| view::draw_serial_prompt(f, batch))?
+        } else {
+            terminal.draw(draw_too_small)?
+        };
 
-    Ok(())
+        match state.event_receiver.recv()? {
+            AppEvent::KeyEvent(KeyEventType::KeyPressed(KeyEvent { key: Some(key), .. })) => {
+                if let Some(serial) = batch.type_key(key) {
+                    return Ok(Some(serial));
+                }
+            }
+            AppEvent::KeyEvent(KeyEventType::KeyReleased(KeyEvent { key: Some(key), .. })) => {
+                batch.release_key(key);
+            }
+            AppEvent::ControlEvent(ControlEventType::Terminate) => return Ok(None),
+            AppEvent::ControlEvent(ControlEventType::Reset) => {
+                batch.serial_input = Some(String::new());
+            }
+            AppEvent::ControlEvent(ControlEventType::StartTest(Some(serial))) => {
+                return Ok(Some(serial));
+            }
+            AppEvent::ControlEvent(ControlEventType::StartTest(None)) => {
+                if let Some(serial) = batch.type_key(Key::Return) {
+                    return Ok(Some(serial));
+                }
+            }
+            AppEvent::ControlEvent(ControlEventType::SelectLayout(layout)) => {
+                switch_layout(state, *layout);
+            }
+            AppEvent::ControlEvent(ControlEventType::ExportReport(path)) => {
+                export_report(state, &path);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Only the evdev backend can tell keyboards apart, the picker is skipped when there is nothing to pick
+fn select_device<B: Backend>(
+    terminal: &mut Terminal<B>,
+    args: &Args,
+) -> Result<MenuResult<Option<InputDevice>>, KbtError> {
+    let devices: Vec<InputDevice> = match args.backend {
+        KeyBackend::Generic if args.device.is_some() => {
+            return Err(KbtError {
+                message: String::from("Picking a keyboard needs the evdev backend"),
+            })
+        }
+        KeyBackend::Generic => return Ok(MenuResult::Selected(None)),
+        #[cfg(target_os = "linux")]
+        KeyBackend::Evdev => evdev_backend::EvdevKeyBackend::list_keyboards(),
+        #[cfg(not(target_os = "linux"))]
+        KeyBackend::Evdev => Vec::new(),
+    };
+
+    if let Some(path) = &args.device {
+        return find_device(devices, path).map(|device| MenuResult::Selected(Some(device)));
+    }
+
+    if devices.len() < 2 {
+        return Ok(MenuResult::Selected(None));
+    }
+
+    match menu::run_device_menu(terminal, devices)? {
+        MenuResult::Selected(DeviceSelection::Device(device)) => {
+            Ok(MenuResult::Selected(Some(device)))
+        }
+        MenuResult::Selected(DeviceSelection::All) => Ok(MenuResult::Selected(None)),
+        MenuResult::Terminate => Ok(MenuResult::Terminate),
+    }
+}
+
+fn select_compare_device(args: &Args) -> Result<Option<InputDevice>, KbtError> {
+    let Some(path) = &args.compare else {
+        return Ok(None);
+    };
+
+    match args.backend {
+        KeyBackend::Generic => Err(KbtError {
+            message: String::from("Comparing keyboards needs the evdev backend"),
+        }),
+        #[cfg(target_os = "linux")]
+        KeyBackend::Evdev => {
+            find_device(evdev_backend::EvdevKeyBackend::list_keyboards(), path).map(Some)
+        }
+        #[cfg(not(target_os = "linux"))]
+        KeyBackend::Evdev => find_device(Vec::new(), path).map(Some),
+    }
+}
+
+fn find_device(devices: Vec<InputDevice>, path: &Path) -> Result<InputDevice, KbtError> {
+    devices
+        .into_iter()
+        .find(|device| device.owns(path))
+        .ok_or(KbtError {
+            message: format!("No readable keyboard at {}", path.display()),
+        })
+}
+
+fn load_layout(selection: &KeyboardSize, extra_rows: &[ExtraRow]) -> KeyboardLayout {
+    let (layout_rows, legends) = match selection {
+        KeyboardSize::Keyboard60 => (
+            keyboard60::ROWS.map(|rows| rows.to_vec()).to_vec(),
+            Legends::Standard,
+        ),
+        KeyboardSize::Keyboard80 => (
+            keyboard80::ROWS.map(|rows| rows.to_vec()).to_vec(),
+            Legends::Standard,
+        ),
+        KeyboardSize::Keyboard100 => (
+            keyboard100::ROWS.map(|rows| rows.to_vec()).to_vec(),
+            Legends::Standard,
+        ),
+        KeyboardSize::KeyboardAppleAnsi => (
+            keyboard_apple_ansi::ROWS.map(|rows| rows.to_vec()).to_vec(),
+            Legends::Mac,
+        ),
+        KeyboardSize::KeyboardAppleIso => (
+            keyboard_apple_iso::ROWS.map(|rows| rows.to_vec()).to_vec(),
+            Legends::Mac,
+        ),
+        KeyboardSize::KeyboardJis => (
+            keyboard_jis::ROWS.map(|rows| rows.to_vec()).to_vec(),
+            Legends::Jis,
+        ),
+        KeyboardSize::KeyboardAbnt2 => (
+            keyboard_abnt2::ROWS.map(|rows| rows.to_vec()).to_vec(),
+            Legends::Abnt2,
+        ),
+        KeyboardSize::KeyboardKorean => (
+            keyboard_korean::ROWS.map(|rows| rows.to_vec()).to_vec(),
+            Legends::Standard,
+        ),
+    };
+
+    prepare_layout(
+        selection.to_string(),
+        with_extra_rows(layout_rows, extra_rows),
+        legends,
+        HashMap::new(),
+    )
 }
 
-fn prepare_layout(rows: Vec<Vec<KeyUI>>) -> KeyboardLayout {
+fn load_layout_file(layout_file: LayoutFile, extra_rows: &[ExtraRow]) -> KeyboardLayout {
+    let rows = with_extra_rows(layout_file.key_rows(), extra_rows);
+    let matrix = layout_file.matrix();
+
+    KeyboardLayout {
+        unbound_keys: layout_file.unbound_keys,
+        ..prepare_layout(layout_file.name, rows, layout_file.legends, matrix)
+    }
+}
+
+fn with_extra_rows(layout_rows: Vec<Vec<KeyUI>>, extra_rows: &[ExtraRow]) -> Vec<Vec<KeyUI>> {
+    extra_rows
+        .iter()
+        .map(|extra_row| match extra_row {
+            ExtraRow::F13F24 => keyboard_extra_rows::F13_F24_ROW.to_vec(),
+            ExtraRow::Media => keyboard_extra_rows::MEDIA_ROW.to_vec(),
+        })
+        .chain(layout_rows)
+        .collect()
+}
+
+fn subscribe_key_backend(
+    backend: KeyBackend,
+    sender: &Sender<AppEvent>,
+) -> Result<Option<(KeyStreamGuard, KeyStreamGuard)>, KbtError> {
+    match backend {
+        KeyBackend::Generic => Ok(Some(GenericKeyBackend::subscribe(sender))),
+        #[cfg(target_os = "linux")]
+        KeyBackend::Evdev => evdev_backend::EvdevKeyBackend::subscribe(sender).map(|_| None),
+        #[cfg(not(target_os = "linux"))]
+        KeyBackend::Evdev => Err(KbtError {
+            message: String::from("evdev backend is only available on Linux"),
+        }),
+    }
+}
+
+fn prepare_layout(
+    name: String,
+    rows: Vec<Vec<KeyUI>>,
+    legends: Legends,
+    matrix: HashMap<Key, MatrixPosition>,
+) -> KeyboardLayout {
     let rows_count: u16 = u16::try_from(rows.len()).unwrap_or(0);
     let height: u16 = rows_count * KEY_HEIGHT;
     let width: u16 = rows
         .iter()
-        .map(|row| row.iter().map(|key| key.size.static_len()).sum())
+        .map(|row| calc_row_width(row))
         .max()
         .unwrap_or(0);
 
@@ -120,33 +816,38 @@ fn prepare_layout(rows: Vec<Vec<KeyUI>>) -> KeyboardLayout {
         rows,
         height,
         width,
-        rows_count,
+        legends,
+        name,
+        matrix,
+        unbound_keys: 0,
     }
 }
 
 fn calc_row_width(row_keys: &[KeyUI]) -> u16 {
     row_keys
         .iter()
-        .map(|key| (key.size.static_len() as i16 + key.size_correction.unwrap_or(0)) as u16)
+        .enumerate()
+        .filter(|(idx, key)| {
+            !VerticalKeyPart::is_stacked_below(
+                idx.checked_sub(1).and_then(|i| row_keys.get(i)),
+                key,
+            )
+        })
+        .map(|(_, key)| (key.size.static_len() as i16 + key.size_correction.unwrap_or(0)) as u16)
         .sum()
 }
 
-fn listen_for_control(sender: Sender<AppEvent>) -> Result<(), KbtError> {
+fn listen_for_control(sender: Sender<AppEvent>, keys: ControlKeys) -> Result<(), KbtError> {
+    let mut listener = ControlListener::new(keys);
     loop {
         match event::read()? {
-            Event::Key(key) => match key.code {
-                KeyCode::Char('c') | KeyCode::Char('q') => {
-                    if key.modifiers == KeyModifiers::CONTROL {
-                        sender.send(AppEvent::ControlEvent(ControlEventType::Terminate))?;
-                        return Ok(());
-                    }
-                }
-                KeyCode::Char('r') => {
-                    if key.modifiers == KeyModifiers::CONTROL {
-                        sender.send(AppEvent::ControlEvent(ControlEventType::Reset))?;
-                    }
+            Event::Key(key) => match listener.control_event(&key, Instant::now()) {
+                Some(ControlEventType::Terminate) => {
+                    sender.send(AppEvent::ControlEvent(ControlEventType::Terminate))?;
+                    return Ok(());
                 }
-                _ => {}
+                Some(control) => sender.send(AppEvent::ControlEvent(control))?,
+                None => {}
             },
             Event::Resize(_, _) => sender.send(AppEvent::ScreenResize)?,
             _ => {}
@@ -154,43 +855,404 @@ fn listen_for_control(sender: Sender<AppEvent>) -> Result<(), KbtError> {
     }
 }
 
-fn run_keyboard<B: Backend>(terminal: &mut Terminal<B>, mut state: App) -> Result<(), KbtError> {
+// Terminal keys that control the session
+#[derive(Clone, Copy)]
+enum ControlKeys {
+    // ctrl+q or ctrl+c quit, ctrl+r resets, ctrl+n ends a batch unit
+    CtrlChords,
+    // The modifier matrix test holds Ctrl over every letter, holding Esc quits instead
+    EscHold,
+}
+
+// Esc must keep repeating for this long, the first repeat comes after the autorepeat delay
+const ESC_HOLD: Duration = Duration::from_millis(1000);
+const ESC_REPEAT_GAP: Duration = Duration::from_millis(750);
+
+struct ControlListener {
+    keys: ControlKeys,
+    // First and latest Esc of the current hold
+    esc_hold: Option<(Instant, Instant)>,
+}
+
+impl ControlListener {
+    fn new(keys: ControlKeys) -> ControlListener {
+        ControlListener {
+            keys,
+            esc_hold: None,
+        }
+    }
+
+    fn control_event(&mut self, key: &event::KeyEvent, at: Instant) -> Option<ControlEventType> {
+        match self.keys {
+            ControlKeys::CtrlChords if key.modifiers == KeyModifiers::CONTROL => match key.code {
+                KeyCode::Char('c') | KeyCode::Char('q') => Some(ControlEventType::Terminate),
+                KeyCode::Char('r') => Some(ControlEventType::Reset),
+                KeyCode::Char('n') => Some(ControlEventType::Finish),
+                _ => None,
+            },
+            ControlKeys::EscHold if key.code == KeyCode::Esc => {
+                let started = match self.esc_hold {
+                    Some((started, latest)) if at.duration_since(latest) <= ESC_REPEAT_GAP => {
+                        started
+                    }
+                    _ => at,
+                };
+                self.esc_hold = Some((started, at));
+
+                (at.duration_since(started) >= ESC_HOLD).then_some(ControlEventType::Terminate)
+            }
+            _ => None,
+        }
+    }
+}
+
+enum SessionEnd {
+    Terminated,
+    // Batch units end on their own once every key was tested
+    Completed,
+}
+
+fn run_keyboard<B: Backend>(
+    terminal: &mut Terminal<B>,
+    state: &mut App,
+) -> Result<SessionEnd, KbtError> {
     // XXX: Drop first event, which is usually release of Return, to have first draw without keys pressed
     _ = state
         .event_receiver
         .recv_timeout(Duration::from_millis(100));
 
+    let mut snapshot = state.event_stream.as_ref().map(|_| Snapshot::new(state));
     loop {
-        if layout_fits(terminal.size()?, &state) {
-            terminal.draw(|f| view::draw(f, &state).expect("Failed to draw"))?
+        stream_changes(state, &mut snapshot);
+
+        if layout_fits(terminal.size()?, state) {
+            terminal.draw(|f| view::draw(f, state).expect("Failed to draw"))?
         } else {
             terminal.draw(draw_too_small)?
         };
 
-        let app_event = state.event_receiver.recv()?;
-        match app_event {
-            AppEvent::KeyEvent(KeyEventType::KeyPressed(key)) => {
-                state.key_states.insert(key, KeyState::Pressed);
+        let app_event = match state.event_receiver.recv_timeout(STUCK_CHECK_INTERVAL) {
+            Ok(app_event) => app_event,
+            Err(RecvTimeoutError::Timeout) => {
+                check_stuck_keys(state);
+                if state.led_test.is_pending() {
+                    refresh_leds(state);
+                }
+                continue;
             }
-            AppEvent::KeyEvent(KeyEventType::KeyReleased(key)) => {
-                if let Some(KeyState::Pressed) = state.key_states.get(&key) {
-                    state.key_states.insert(key, KeyState::Released);
-                    {}
+            Err(err) => return Err(err.into()),
+        };
+        match app_event {
+            AppEvent::KeyEvent(key_event) => {
+                let timestamp = key_event.event().timestamp;
+                if let Some(board) = state
+                    .compare
+                    .as_mut()
+                    .filter(|board| board.accepts(key_event.event()))
+                {
+                    log::info!("{} on {}", key_event, board.device);
+                    board.record(&key_event, timestamp);
+                    if let Some(stream) = &state.event_stream {
+                        stream.emit(&StreamEvent::key_event(&key_event, state.started_at));
+                    }
+                    continue;
+                }
+                if !state.accepts(key_event.event()) {
+                    continue;
+                }
+
+                log::info!("{}", key_event);
+                if let Some(stream) = &state.event_stream {
+                    stream.emit(&StreamEvent::key_event(&key_event, state.started_at));
+                }
+                // Only evdev timestamps come from the input subsystem
+                if key_event.event().device.is_some() {
+                    state.polling.record(timestamp);
+                }
+                match &key_event {
+                    KeyEventType::KeyPressed(KeyEvent {
+                        key: Some(key),
+                        raw_code,
+                        ..
+                    }) => {
+                        if let Some(raw_code) = raw_code {
+                            state.record_raw_code(*key, raw_code);
+                        }
+                        state.press_key(*key, timestamp);
+                    }
+                    KeyEventType::KeyReleased(KeyEvent { key: Some(key), .. }) => {
+                        state.release_key(*key, timestamp);
+                    }
+                    KeyEventType::KeyPressed(KeyEvent {
+                        key: None,
+                        raw_code: Some(raw_code),
+                        ..
+                    }) => {
+                        state
+                            .unmapped_key_states
+                            .insert(raw_code.clone(), KeyState::Pressed);
+                    }
+                    KeyEventType::KeyReleased(KeyEvent {
+                        key: None,
+                        raw_code: Some(raw_code),
+                        ..
+                    }) => {
+                        state
+                            .unmapped_key_states
+                            .insert(raw_code.clone(), KeyState::Released);
+                    }
+                    // Only evdev reports keys kbt has no `Key` for, always with their code
+                    KeyEventType::KeyPressed(KeyEvent {
+                        key: None,
+                        raw_code: None,
+                        ..
+                    })
+                    | KeyEventType::KeyReleased(KeyEvent {
+                        key: None,
+                        raw_code: None,
+                        ..
+                    }) => {}
+                }
+                if let Some(matrix) = &mut state.modifier_matrix {
+                    matrix.record(&key_event);
+                }
+                state.last_key_event = Some(key_event);
+                if state.led_test.is_pending() {
+                    refresh_leds(state);
+                }
+                if state.batch.is_some() && state.is_complete() {
+                    stream_changes(state, &mut snapshot);
+                    return Ok(SessionEnd::Completed);
                 }
             }
             AppEvent::ControlEvent(control) => match control {
                 ControlEventType::Terminate => {
-                    return Ok(());
+                    return Ok(SessionEnd::Terminated);
                 }
                 ControlEventType::Reset => {
                     state.reset();
+                    if let Some(stream) = &state.event_stream {
+                        stream.emit(&StreamEvent::Reset);
+                    }
+                }
+                ControlEventType::Finish if state.batch.is_some() => {
+                    return Ok(SessionEnd::Completed);
                 }
+                ControlEventType::Finish => {}
+                ControlEventType::SelectLayout(layout) => switch_layout(state, *layout),
+                ControlEventType::StartTest(serial) => {
+                    state.reset();
+                    if serial.is_some() {
+                        state.serial = serial;
+                    }
+                    if let Some(stream) = &state.event_stream {
+                        stream.emit(&StreamEvent::TestStarted {
+                            serial: state.serial.as_deref(),
+                        });
+                    }
+                }
+                ControlEventType::ExportReport(path) => export_report(state, &path),
             },
             AppEvent::ScreenResize => {}
         }
     }
 }
 
+fn switch_layout(state: &mut App, layout: KeyboardLayout) {
+    state.layout = layout;
+    state.reset();
+    if let Some(stream) = &state.event_stream {
+        stream.emit(&StreamEvent::LayoutSelected {
+            layout: &state.layout.name,
+            keys: state.layout.keys().len(),
+        });
+    }
+}
+
+fn export_report(state: &App, path: &Path) {
+    match Report::new(state).write(path) {
+        Ok(_) => {
+            if let Some(stream) = &state.event_stream {
+                stream.emit(&StreamEvent::ReportExported { path });
+            }
+        }
+        Err(err) => log::error!("Failed to export the report: {}", err.message),
+    }
+}
+
+fn stream_changes(state: &App, snapshot: &mut Option<Snapshot>) {
+    if let (Some(snapshot), Some(stream)) = (snapshot, &state.event_stream) {
+        snapshot.update(state, stream);
+    }
+}
+
+fn run_editor<B: Backend>(
+    terminal: &mut Terminal<B>,
+    receiver: &Receiver<AppEvent>,
+    editor: &mut LayoutEditor,
+    locale: Option<Locale>,
+) -> Result<(), KbtError> {
+    // XXX: Drop first event, which is usually release of Return, to have first draw without keys pressed
+    _ = receiver.recv_timeout(Duration::from_millis(100));
+
+    loop {
+        let layout = editor.layout();
+        if view::editor_fits(terminal.size()?, &layout) {
+            terminal
+                .draw(|f| view::draw_editor(f, editor, &layout, locale).expect("Failed to draw"))?
+        } else {
+            terminal.draw(draw_too_small)?
+        };
+
+        match receiver.recv()? {
+            AppEvent::KeyEvent(key_event) => {
+                log::info!("{}", key_event);
+                if let KeyEventType::KeyPressed(event) = &key_event {
+                    editor.press(event);
+                }
+            }
+            AppEvent::ControlEvent(ControlEventType::Terminate) => return Ok(()),
+            AppEvent::ControlEvent(ControlEventType::Reset) => editor.revert(),
+            AppEvent::ControlEvent(_) | AppEvent::ScreenResize => {}
+        }
+    }
+}
+
+fn run_learner<B: Backend>(
+    terminal: &mut Terminal<B>,
+    receiver: &Receiver<AppEvent>,
+    learner: &mut LayoutLearner,
+    locale: Option<Locale>,
+) -> Result<(), KbtError> {
+    // XXX: Drop first event, which is usually release of Return, to have first draw without keys pressed
+    _ = receiver.recv_timeout(Duration::from_millis(100));
+
+    loop {
+        let layout = learner.layout();
+        if view::learner_fits(terminal.size()?, &layout) {
+            terminal.draw(|f| {
+                view::draw_learner(f, learner, &layout, locale).expect("Failed to draw")
+            })?
+        } else {
+            terminal.draw(draw_too_small)?
+        };
+
+        match receiver.recv()? {
+            AppEvent::KeyEvent(key_event) => {
+                log::info!("{}", key_event);
+                match &key_event {
+                    KeyEventType::KeyPressed(event) => learner.press(event)?,
+                    KeyEventType::KeyReleased(event) => learner.release(event),
+                }
+            }
+            AppEvent::ControlEvent(ControlEventType::Terminate) => return learner.end_row(),
+            AppEvent::ControlEvent(ControlEventType::Reset) => learner.reset(),
+            AppEvent::ControlEvent(ControlEventType::Finish) => learner.end_row()?,
+            AppEvent::ControlEvent(_) | AppEvent::ScreenResize => {}
+        }
+    }
+}
+
+fn check_stuck_keys(state: &mut App) {
+    let now = Instant::now();
+    if state.has_stuck_candidates(now) {
+        let pressed_now = poll_pressed_keys(state.backend, state.device.as_ref());
+        state.flag_stuck_keys(pressed_now.as_ref(), now);
+    }
+}
+
+fn poll_pressed_keys(backend: KeyBackend, device: Option<&InputDevice>) -> Option<HashSet<Key>> {
+    match backend {
+        KeyBackend::Generic => Some(GenericKeyBackend::pressed_keys()),
+        #[cfg(target_os = "linux")]
+        KeyBackend::Evdev => evdev_backend::EvdevKeyBackend::pressed_keys(device)
+            .inspect_err(|err| log::error!("Failed to poll key state: {}", err.message))
+            .ok(),
+        #[cfg(not(target_os = "linux"))]
+        KeyBackend::Evdev => None,
+    }
+}
+
+fn refresh_leds(state: &mut App) {
+    let leds = state.led_source.read();
+    state.update_leds(leds, Instant::now());
+}
+
+// xset needs a running X server, evdev devices are kept open for the whole session
+fn open_led_source(backend: KeyBackend, device: Option<&InputDevice>) -> LedSource {
+    match backend {
+        KeyBackend::Generic if env::var_os("DISPLAY").is_some() => LedSource::X,
+        KeyBackend::Generic => LedSource::Unavailable,
+        #[cfg(target_os = "linux")]
+        KeyBackend::Evdev => LedSource::Evdev(evdev_backend::EvdevLeds::open(device)),
+        #[cfg(not(target_os = "linux"))]
+        KeyBackend::Evdev => LedSource::Unavailable,
+    }
+}
+
 fn layout_fits(terminal_size: Rect, state: &App) -> bool {
-    terminal_size.width > state.layout.width && terminal_size.height > state.layout.height
+    match (&state.modifier_matrix, state.mode) {
+        (Some(matrix), _) => view::modifier_matrix_fits(terminal_size, matrix),
+        (None, TestMode::Matrix) => view::switch_matrix_fits(terminal_size, &state.layout),
+        (None, _) => view::arrange_boards(terminal_size, state).is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyEvent;
+
+    use super::*;
+
+    fn ctrl(letter: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(letter), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn ctrl_sweep_in_modifiers_mode_keeps_the_session() {
+        let mut listener = ControlListener::new(ControlKeys::EscHold);
+        let start = Instant::now();
+
+        for (idx, letter) in ('a'..='z').enumerate() {
+            let at = start + Duration::from_millis(idx as u64 * 50);
+            assert!(listener.control_event(&ctrl(letter), at).is_none());
+        }
+    }
+
+    #[test]
+    fn ctrl_chords_control_the_keys_test() {
+        let mut listener = ControlListener::new(ControlKeys::CtrlChords);
+        let at = Instant::now();
+
+        assert!(matches!(
+            listener.control_event(&ctrl('c'), at),
+            Some(ControlEventType::Terminate)
+        ));
+        assert!(matches!(
+            listener.control_event(&ctrl('r'), at),
+            Some(ControlEventType::Reset)
+        ));
+        assert!(listener.control_event(&ctrl('a'), at).is_none());
+    }
+
+    #[test]
+    fn held_esc_quits_the_modifiers_test() {
+        let mut listener = ControlListener::new(ControlKeys::EscHold);
+        let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        let start = Instant::now();
+
+        // A tap, then a hold with the usual autorepeat delay and rate
+        assert!(listener.control_event(&esc, start).is_none());
+        let hold = start + Duration::from_secs(5);
+        assert!(listener.control_event(&esc, hold).is_none());
+        let repeats: Vec<Option<ControlEventType>> = (0..15)
+            .map(|repeat| {
+                let at = hold + Duration::from_millis(600 + repeat * 40);
+                listener.control_event(&esc, at)
+            })
+            .collect();
+
+        assert!(repeats[..10].iter().all(Option::is_none));
+        assert!(matches!(repeats[10], Some(ControlEventType::Terminate)));
+    }
 }
@@ -7,28 +7,60 @@ use ratatui::{
     Frame, Terminal,
 };
 
-use crate::{KbtError, KeyboardSize, MenuResult};
+use std::fmt::Display;
 
-struct MenuState {
-    selections: Vec<KeyboardSize>,
+use crate::{DeviceSelection, InputDevice, KbtError, KeyboardSize, MenuResult};
+
+struct MenuState<T> {
+    title: String,
+    selections: Vec<T>,
     cursor: usize,
 }
 
-impl Default for MenuState {
+impl Default for MenuState<KeyboardSize> {
     fn default() -> Self {
         MenuState {
+            title: String::from("kbt"),
             selections: vec![
                 KeyboardSize::Keyboard60,
                 KeyboardSize::Keyboard80,
                 KeyboardSize::Keyboard100,
+                KeyboardSize::KeyboardAppleAnsi,
+                KeyboardSize::KeyboardAppleIso,
+                KeyboardSize::KeyboardJis,
+                KeyboardSize::KeyboardAbnt2,
+                KeyboardSize::KeyboardKorean,
             ],
             cursor: 0,
         }
     }
 }
 
-pub fn run_menu<B: Backend>(terminal: &mut Terminal<B>) -> Result<MenuResult, KbtError> {
-    let mut state = MenuState::default();
+pub fn run_menu<B: Backend>(
+    terminal: &mut Terminal<B>,
+) -> Result<MenuResult<KeyboardSize>, KbtError> {
+    run_selection_menu(terminal, MenuState::default())
+}
+
+pub fn run_device_menu<B: Backend>(
+    terminal: &mut Terminal<B>,
+    devices: Vec<InputDevice>,
+) -> Result<MenuResult<DeviceSelection>, KbtError> {
+    let state = MenuState {
+        title: String::from("select keyboard device"),
+        selections: std::iter::once(DeviceSelection::All)
+            .chain(devices.into_iter().map(DeviceSelection::Device))
+            .collect(),
+        cursor: 0,
+    };
+
+    run_selection_menu(terminal, state)
+}
+
+fn run_selection_menu<B: Backend, T: Display + Clone>(
+    terminal: &mut Terminal<B>,
+    mut state: MenuState<T>,
+) -> Result<MenuResult<T>, KbtError> {
     let max_selection_idx = state.selections.len() - 1;
 
     loop {
@@ -51,7 +83,7 @@ pub fn run_menu<B: Backend>(terminal: &mut Terminal<B>) -> Result<MenuResult, Kb
                     }
                 }
                 (KeyEventKind::Press, KeyCode::Enter) => {
-                    return Ok(MenuResult::KeyboardSelected(
+                    return Ok(MenuResult::Selected(
                         state
                             .selections
                             .get(state.cursor)
@@ -61,10 +93,10 @@ pub fn run_menu<B: Backend>(terminal: &mut Terminal<B>) -> Result<MenuResult, Kb
                             .clone(),
                     ))
                 }
-                (KeyEventKind::Press, KeyCode::Char('c') | KeyCode::Char('q')) => {
-                    if key.modifiers == KeyModifiers::CONTROL {
-                        return Ok(MenuResult::Terminate);
-                    }
+                (KeyEventKind::Press, KeyCode::Char('c') | KeyCode::Char('q'))
+                    if key.modifiers == KeyModifiers::CONTROL =>
+                {
+                    return Ok(MenuResult::Terminate);
                 }
                 _ => {}
             }
@@ -72,7 +104,7 @@ pub fn run_menu<B: Backend>(terminal: &mut Terminal<B>) -> Result<MenuResult, Kb
     }
 }
 
-fn view_menu(frame: &mut Frame, state: &MenuState) -> Result<(), KbtError> {
+fn view_menu<T: Display>(frame: &mut Frame, state: &MenuState<T>) -> Result<(), KbtError> {
     let items: Vec<ListItem> = state
         .selections
         .iter()
@@ -94,8 +126,17 @@ fn view_menu(frame: &mut Frame, state: &MenuState) -> Result<(), KbtError> {
 
     let terminal_size: Rect = frame.size();
 
-    let layout_height: u16 = 5;
-    let layout_width: u16 = 15;
+    let list_height: u16 = u16::try_from(state.selections.len())?;
+    let layout_height: u16 = 2 + list_height;
+    let longest_item: usize = state
+        .selections
+        .iter()
+        .map(|selection| selection.to_string().chars().count())
+        .max()
+        .unwrap_or(0);
+    let layout_width: u16 = u16::try_from(longest_item + 2)?
+        .max(21)
+        .min(terminal_size.width);
     let left_padding: u16 = (terminal_size.width / 2) - (layout_width / 2);
     let top_padding: u16 = (terminal_size.height / 2) - (layout_height / 2);
 
@@ -103,10 +144,10 @@ fn view_menu(frame: &mut Frame, state: &MenuState) -> Result<(), KbtError> {
 
     let layout_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(2), Constraint::Length(2)].as_ref())
+        .constraints([Constraint::Length(2), Constraint::Length(list_height)].as_ref())
         .split(rect);
 
-    let title = Paragraph::new("kbt").style(
+    let title = Paragraph::new(state.title.as_str()).style(
         Style::default()
             .fg(Color::Green)
             .add_modifier(Modifier::ITALIC),
@@ -115,7 +156,7 @@ fn view_menu(frame: &mut Frame, state: &MenuState) -> Result<(), KbtError> {
     // render title
     frame.render_widget(
         title,
-        *layout_chunks.get(0).ok_or(KbtError {
+        *layout_chunks.first().ok_or(KbtError {
             message: String::from("Failed to get correct layout chunk for title"),
         })?,
     );
@@ -1,18 +1,100 @@
-use std::{collections::HashMap, fmt::Display, sync::mpsc::Receiver};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
 
-use crate::key::Key;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    batch::Batch,
+    event_stream::EventStream,
+    ghosting::{self, MatrixPosition},
+    key::Key,
+    leds::{LedSource, LedTest, LockKey, LockLeds},
+    locale::Locale,
+    modifier_matrix::ModifierMatrix,
+    polling::PollingStats,
+};
 
 pub struct App {
     pub key_states: HashMap<Key, KeyState>,
     pub event_receiver: Receiver<AppEvent>,
     pub layout: KeyboardLayout,
+    pub locale: Option<Locale>,
+    pub last_key_event: Option<KeyEventType>,
+    pub unmapped_key_states: HashMap<RawKeyCode, KeyState>,
+    pub device: Option<InputDevice>,
+    pub key_timings: KeyTimings,
+    pub polling: PollingStats,
+    pub backend: KeyBackend,
+    pub stuck_threshold: Duration,
+    pub stuck_keys: HashMap<Key, StuckKey>,
+    // Every key that lost a release during the session, kept after the key recovers
+    pub lost_releases: HashSet<Key>,
+    // None when the backend can't read LEDs
+    pub leds: Option<LockLeds>,
+    pub led_source: LedSource,
+    pub led_test: LedTest,
+    pub modifier_matrix: Option<ModifierMatrix>,
+    // Keys whose press looked like a matrix ghost
+    pub ghost_keys: HashSet<Key>,
+    pub mode: TestMode,
+    pub compare: Option<CompareBoard>,
+    // Every code the backend reported a key with, some boards send the same key under several
+    pub raw_codes: HashMap<Key, BTreeSet<String>>,
+    pub started_at: Instant,
+    // Serial number of the board under test, when the user gave one
+    pub serial: Option<String>,
+    pub batch: Option<Batch>,
+    pub event_stream: Option<EventStream>,
+}
+
+#[derive(Clone, Copy)]
+pub enum StuckKey {
+    // A fresh device state poll agrees that the key is down
+    Held,
+    // The device says the key is up, its release event never arrived
+    ReleaseLost,
+}
+
+// Second keyboard tested side by side with the first one, e.g. a known-good reference board
+pub struct CompareBoard {
+    pub device: InputDevice,
+    pub key_states: HashMap<Key, KeyState>,
+    pub key_timings: KeyTimings,
 }
 
+#[derive(Default)]
+pub struct KeyTimings {
+    pressed_at: HashMap<Key, Instant>,
+    released_at: HashMap<Key, Instant>,
+    pub hold_times: HashMap<Key, Duration>,
+    pub hold_history: HashMap<Key, Vec<Duration>>,
+    pub presses: HashMap<Key, u32>,
+    // Presses that came right after a release of the same key, a bouncing switch
+    pub chatter: HashMap<Key, u32>,
+}
+
+// Hold times only count as different when one is over twice the other and they are this far apart
+const HOLD_TIME_TOLERANCE: Duration = Duration::from_millis(50);
+
+// Nobody presses a key again this fast on purpose
+const CHATTER_WINDOW: Duration = Duration::from_millis(30);
+
 pub struct KeyboardLayout {
+    pub name: String,
     pub rows: Vec<Row>,
     pub height: u16,
     pub width: u16,
-    pub rows_count: u16,
+    pub legends: Legends,
+    // Only known for layouts loaded from a file
+    pub matrix: HashMap<Key, MatrixPosition>,
+    // Keys of an imported layout without a kbt key, drawn as gaps
+    pub unbound_keys: usize,
 }
 
 pub struct Row {
@@ -22,7 +104,238 @@ pub struct Row {
 
 impl App {
     pub fn reset(&mut self) {
-        self.key_states = HashMap::new()
+        self.key_states = HashMap::new();
+        self.last_key_event = None;
+        self.unmapped_key_states = HashMap::new();
+        self.key_timings = KeyTimings::default();
+        self.polling = PollingStats::default();
+        self.stuck_keys = HashMap::new();
+        self.lost_releases = HashSet::new();
+        self.led_test = LedTest::default();
+        self.ghost_keys = HashSet::new();
+        self.raw_codes = HashMap::new();
+        self.started_at = Instant::now();
+        if self.modifier_matrix.is_some() {
+            self.modifier_matrix = Some(ModifierMatrix::new(&self.layout));
+        }
+        if let Some(board) = &mut self.compare {
+            board.key_states = HashMap::new();
+            board.key_timings = KeyTimings::default();
+        }
+    }
+
+    // Every key of the layout was pressed and is up again
+    pub fn is_complete(&self) -> bool {
+        self.layout
+            .keys()
+            .iter()
+            .all(|key| matches!(self.key_states.get(key), Some(KeyState::Released)))
+    }
+
+    pub fn accepts(&self, key_event: &KeyEvent) -> bool {
+        match (&self.device, &key_event.device) {
+            (Some(device), Some(event_device)) => device.owns(event_device),
+            _ => true,
+        }
+    }
+
+    pub fn press_key(&mut self, key: Key, at: Instant) {
+        if let Some(KeyState::Pressed) = self.key_states.get(&key) {
+            self.stuck_keys.insert(key, StuckKey::ReleaseLost);
+            self.lost_releases.insert(key);
+        }
+        if ghosting::is_ghost_press(&self.layout.matrix, &self.held_keys(), key) {
+            self.ghost_keys.insert(key);
+        }
+        if let (Some(lock_key), Some(leds)) = (LockKey::from_key(key), &self.leds) {
+            self.led_test.lock_pressed(lock_key, leds, at);
+        }
+        self.key_states.insert(key, KeyState::Pressed);
+        self.key_timings.press(key, at);
+    }
+
+    pub fn record_raw_code(&mut self, key: Key, raw_code: &RawKeyCode) {
+        self.raw_codes
+            .entry(key)
+            .or_default()
+            .insert(raw_code.to_string());
+    }
+
+    pub fn held_keys(&self) -> Vec<Key> {
+        self.key_states
+            .iter()
+            .filter(|(_, key_state)| matches!(key_state, KeyState::Pressed))
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    pub fn release_key(&mut self, key: Key, at: Instant) {
+        if let Some(KeyState::Pressed) = self.key_states.get(&key) {
+            self.key_states.insert(key, KeyState::Released);
+        }
+        self.key_timings.release(key, at);
+        self.stuck_keys.remove(&key);
+    }
+
+    pub fn update_leds(&mut self, leds: Option<LockLeds>, now: Instant) {
+        if let Some(leds) = &leds {
+            self.led_test.resolve(leds, now);
+        }
+        self.leds = leds;
+    }
+
+    // `pressed_now` is a fresh poll of the device state, None when the backend couldn't tell
+    pub fn flag_stuck_keys(&mut self, pressed_now: Option<&HashSet<Key>>, now: Instant) {
+        for key in self.key_timings.held_longer_than(self.stuck_threshold, now) {
+            let stuck_key = match pressed_now {
+                Some(pressed_now) if !pressed_now.contains(&key) => StuckKey::ReleaseLost,
+                _ => StuckKey::Held,
+            };
+            if let StuckKey::ReleaseLost = stuck_key {
+                self.lost_releases.insert(key);
+            }
+            self.stuck_keys.insert(key, stuck_key);
+        }
+    }
+
+    pub fn has_stuck_candidates(&self, now: Instant) -> bool {
+        !self
+            .key_timings
+            .held_longer_than(self.stuck_threshold, now)
+            .is_empty()
+    }
+
+    // A key differs when only one of the compared boards registered it, or their hold times are far apart
+    pub fn differs(&self, key: &Key) -> bool {
+        let Some(board) = &self.compare else {
+            return false;
+        };
+
+        let registered = |key_state: Option<&KeyState>| {
+            matches!(key_state, Some(KeyState::Pressed | KeyState::Released))
+        };
+        if registered(self.key_states.get(key)) != registered(board.key_states.get(key)) {
+            return true;
+        }
+
+        match (
+            self.key_timings.hold_times.get(key),
+            board.key_timings.hold_times.get(key),
+        ) {
+            (Some(a), Some(b)) => {
+                let (shorter, longer) = if a < b { (a, b) } else { (b, a) };
+                *longer - *shorter > HOLD_TIME_TOLERANCE && *longer > *shorter * 2
+            }
+            _ => false,
+        }
+    }
+
+    pub fn differing_keys(&self) -> Vec<Key> {
+        self.layout
+            .keys()
+            .into_iter()
+            .filter(|key| self.differs(key))
+            .collect()
+    }
+
+    // Pressed keys that have no place on the drawn layout, including ones kbt has no `Key` for
+    pub fn extra_keys(&self) -> Vec<(String, &KeyState)> {
+        let mut extra_keys: Vec<(String, &KeyState)> = self
+            .key_states
+            .iter()
+            .filter(|(key, _)| !self.layout.contains(key))
+            .map(|(key, key_state)| (format!("{:?}", key), key_state))
+            .chain(
+                self.unmapped_key_states
+                    .iter()
+                    .map(|(raw_code, key_state)| (raw_code.to_string(), key_state)),
+            )
+            .collect();
+        extra_keys.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        extra_keys
+    }
+}
+
+impl CompareBoard {
+    pub fn new(device: InputDevice) -> CompareBoard {
+        CompareBoard {
+            device,
+            key_states: HashMap::new(),
+            key_timings: KeyTimings::default(),
+        }
+    }
+
+    pub fn accepts(&self, key_event: &KeyEvent) -> bool {
+        key_event
+            .device
+            .as_ref()
+            .is_some_and(|path| self.device.owns(path))
+    }
+
+    // Keys kbt has no `Key` for are only tracked for the main board
+    pub fn record(&mut self, key_event: &KeyEventType, at: Instant) {
+        match key_event {
+            KeyEventType::KeyPressed(KeyEvent { key: Some(key), .. }) => {
+                self.key_states.insert(*key, KeyState::Pressed);
+                self.key_timings.press(*key, at);
+            }
+            KeyEventType::KeyReleased(KeyEvent { key: Some(key), .. }) => {
+                if let Some(KeyState::Pressed) = self.key_states.get(key) {
+                    self.key_states.insert(*key, KeyState::Released);
+                }
+                self.key_timings.release(*key, at);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl KeyTimings {
+    pub fn press(&mut self, key: Key, at: Instant) {
+        if let Some(released_at) = self.released_at.get(&key) {
+            if at.saturating_duration_since(*released_at) < CHATTER_WINDOW {
+                *self.chatter.entry(key).or_insert(0) += 1;
+            }
+        }
+        self.pressed_at.insert(key, at);
+        *self.presses.entry(key).or_insert(0) += 1;
+    }
+
+    pub fn release(&mut self, key: Key, at: Instant) {
+        self.released_at.insert(key, at);
+        if let Some(pressed_at) = self.pressed_at.remove(&key) {
+            let hold_time = at.saturating_duration_since(pressed_at);
+            self.hold_times.insert(key, hold_time);
+            self.hold_history.entry(key).or_default().push(hold_time);
+        }
+    }
+
+    pub fn held_longer_than(&self, threshold: Duration, now: Instant) -> Vec<Key> {
+        self.pressed_at
+            .iter()
+            .filter(|(_, pressed_at)| now.saturating_duration_since(**pressed_at) > threshold)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+}
+
+impl KeyboardLayout {
+    pub fn keys(&self) -> Vec<Key> {
+        let mut keys: Vec<Key> = Vec::new();
+        for key in self.rows.iter().flat_map(|row| row.keys.iter()) {
+            if key.key != Key::Separator && !keys.contains(&key.key) {
+                keys.push(key.key);
+            }
+        }
+
+        keys
+    }
+
+    pub fn contains(&self, key: &Key) -> bool {
+        self.rows
+            .iter()
+            .any(|row| row.keys.iter().any(|ui_key| ui_key.key == *key))
     }
 }
 
@@ -34,16 +347,46 @@ pub struct KeyUI {
     pub vertical_key_part: Option<VerticalKeyPart>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum VerticalKeyPart {
     Top,
     Bottom,
+    // Half-height keys, e.g. Apple arrows. A LowerHalf right after an UpperHalf shares its slot.
+    UpperHalf,
+    LowerHalf,
 }
 
-#[derive(Clone, Copy)]
+impl VerticalKeyPart {
+    pub fn is_stacked_below(previous: Option<&KeyUI>, current: &KeyUI) -> bool {
+        matches!(
+            (
+                previous.and_then(|key| key.vertical_key_part),
+                current.vertical_key_part
+            ),
+            (
+                Some(VerticalKeyPart::UpperHalf),
+                Some(VerticalKeyPart::LowerHalf)
+            )
+        )
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Legends {
+    #[default]
+    Standard,
+    Mac,
+    Jis,
+    Abnt2,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
 pub enum KeySize {
     U05,
     U1,
+    U125,
     U15,
     U175,
     U2,
@@ -51,6 +394,7 @@ pub enum KeySize {
     U250,
     U275,
     U4,
+    U5,
     U675,
 }
 
@@ -66,10 +410,38 @@ pub enum KeySize {
 // ┃  |<-   ┃
 // ┗━━━━━━━━┛
 impl KeySize {
+    pub const ALL: [KeySize; 12] = [
+        KeySize::U05,
+        KeySize::U1,
+        KeySize::U125,
+        KeySize::U15,
+        KeySize::U175,
+        KeySize::U2,
+        KeySize::U225,
+        KeySize::U250,
+        KeySize::U275,
+        KeySize::U4,
+        KeySize::U5,
+        KeySize::U675,
+    ];
+
+    // Largest size that fits into `cells`, with the correction that makes up the rest
+    pub fn fitting(cells: u16) -> (KeySize, Option<i16>) {
+        let size = KeySize::ALL
+            .into_iter()
+            .rev()
+            .find(|size| size.static_len() <= cells)
+            .unwrap_or(KeySize::U05);
+        let correction = cells as i16 - size.static_len() as i16;
+
+        (size, (correction != 0).then_some(correction))
+    }
+
     pub fn static_len(&self) -> u16 {
         match self {
             KeySize::U05 => 2,
             KeySize::U1 => 5,
+            KeySize::U125 => 6,
             KeySize::U15 => 7,
             KeySize::U175 => 8,
             KeySize::U2 => 10,
@@ -77,30 +449,140 @@ impl KeySize {
             KeySize::U250 => 12,
             KeySize::U275 => 14,
             KeySize::U4 => 20,
+            KeySize::U5 => 25,
             KeySize::U675 => 34,
         }
     }
 }
 
-pub enum MenuResult {
-    KeyboardSelected(KeyboardSize),
+pub enum MenuResult<T> {
+    Selected(T),
     Terminate,
 }
 
+impl<T> MenuResult<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> MenuResult<U> {
+        match self {
+            MenuResult::Selected(selection) => MenuResult::Selected(f(selection)),
+            MenuResult::Terminate => MenuResult::Terminate,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct InputDevice {
+    pub path: PathBuf,
+    pub name: String,
+    // Every event node of the physical keyboard, media keys often come from a node of their own
+    pub nodes: Vec<PathBuf>,
+}
+
+impl InputDevice {
+    pub fn owns(&self, node: &Path) -> bool {
+        self.nodes.iter().any(|path| path == node)
+    }
+}
+
+impl Display for InputDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.path.display())
+    }
+}
+
+#[derive(Clone)]
+pub enum DeviceSelection {
+    All,
+    Device(InputDevice),
+}
+
+impl Display for DeviceSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceSelection::All => write!(f, "All keyboards"),
+            DeviceSelection::Device(device) => write!(f, "{}", device),
+        }
+    }
+}
+
 pub enum KeyState {
     Pressed,
     Released,
     Untouched,
 }
 
+#[derive(Clone)]
 pub enum KeyEventType {
-    KeyPressed(Key),
-    KeyReleased(Key),
+    KeyPressed(KeyEvent),
+    KeyReleased(KeyEvent),
+}
+
+impl KeyEventType {
+    pub fn event(&self) -> &KeyEvent {
+        match self {
+            KeyEventType::KeyPressed(event) | KeyEventType::KeyReleased(event) => event,
+        }
+    }
+}
+
+impl Display for KeyEventType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = match self {
+            KeyEventType::KeyPressed(_) => "pressed",
+            KeyEventType::KeyReleased(_) => "released",
+        };
+        let event = self.event();
+        let key = event
+            .key
+            .map_or(String::from("unmapped"), |key| format!("{:?}", key));
+
+        write!(f, "{} {} [{}]", key, action, event.raw_code_label())
+    }
+}
+
+// Key as seen by kbt plus the code the backend reported it with. Unmapped keys only have the latter.
+#[derive(Clone)]
+pub struct KeyEvent {
+    pub key: Option<Key>,
+    // device_query hides the platform code behind its own key names, only evdev has one
+    pub raw_code: Option<RawKeyCode>,
+    // Only backends that read devices one by one know where the event came from
+    pub device: Option<PathBuf>,
+    // Taken when the backend saw the event, not when the render loop got to it
+    pub timestamp: Instant,
+}
+
+impl KeyEvent {
+    pub fn raw_code_label(&self) -> String {
+        self.raw_code.as_ref().map_or_else(
+            || String::from("raw codes need the evdev backend"),
+            RawKeyCode::to_string,
+        )
+    }
+}
+
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub enum RawKeyCode {
+    Evdev { code: u16, name: String },
+}
+
+impl Display for RawKeyCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawKeyCode::Evdev { code, name } => write!(f, "evdev {} {}", code, name),
+        }
+    }
 }
 
 pub enum ControlEventType {
     Terminate,
     Reset,
+    // Ends the unit under test of a batch run, untested keys count against it
+    Finish,
+    // Switches to another built-in layout and starts over
+    SelectLayout(Box<KeyboardLayout>),
+    // Starts over, optionally with the serial of the next board
+    StartTest(Option<String>),
+    ExportReport(PathBuf),
 }
 
 pub enum AppEvent {
@@ -109,11 +591,48 @@ pub enum AppEvent {
     ScreenResize,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyBackend {
+    Generic,
+    Evdev,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum TestMode {
+    // Press every key on the layout
+    Keys,
+    // Hold each modifier and sweep the alpha keys
+    Modifiers,
+    // Show held keys on the switch matrix of a layout file, to find ghosting
+    Matrix,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExtraRow {
+    #[value(name = "f13-f24")]
+    F13F24,
+    Media,
+}
+
+#[derive(Clone, Debug, ValueEnum)]
 pub enum KeyboardSize {
+    #[value(name = "60")]
     Keyboard60,
+    #[value(name = "80")]
     Keyboard80,
+    #[value(name = "100")]
     Keyboard100,
+    #[value(name = "apple-ansi")]
+    KeyboardAppleAnsi,
+    #[value(name = "apple-iso")]
+    KeyboardAppleIso,
+    #[value(name = "jis")]
+    KeyboardJis,
+    #[value(name = "abnt2")]
+    KeyboardAbnt2,
+    #[value(name = "korean")]
+    KeyboardKorean,
 }
 
 impl Display for KeyboardSize {
@@ -122,6 +641,11 @@ impl Display for KeyboardSize {
             KeyboardSize::Keyboard60 => write!(f, "60% layout"),
             KeyboardSize::Keyboard80 => write!(f, "80% layout"),
             KeyboardSize::Keyboard100 => write!(f, "100% layout"),
+            KeyboardSize::KeyboardAppleAnsi => write!(f, "Apple ANSI layout"),
+            KeyboardSize::KeyboardAppleIso => write!(f, "Apple ISO layout"),
+            KeyboardSize::KeyboardJis => write!(f, "JIS layout"),
+            KeyboardSize::KeyboardAbnt2 => write!(f, "ABNT2 layout"),
+            KeyboardSize::KeyboardKorean => write!(f, "Korean layout"),
         }
     }
 }
@@ -138,3 +662,34 @@ impl<T: ToString> From<T> for KbtError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_presses_within_the_chatter_window() {
+        let start = Instant::now();
+        let mut key_timings = KeyTimings::default();
+
+        key_timings.press(Key::A, start);
+        key_timings.release(Key::A, start + Duration::from_millis(80));
+        key_timings.press(Key::A, start + Duration::from_millis(100));
+        key_timings.release(Key::A, start + Duration::from_millis(150));
+        key_timings.press(Key::A, start + Duration::from_millis(180));
+
+        assert_eq!(key_timings.chatter.get(&Key::A), Some(&1));
+        assert_eq!(
+            key_timings.hold_times.get(&Key::A),
+            Some(&Duration::from_millis(50))
+        );
+    }
+
+    #[test]
+    fn a_first_press_is_no_chatter() {
+        let mut key_timings = KeyTimings::default();
+        key_timings.press(Key::A, Instant::now());
+
+        assert!(key_timings.chatter.is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{
+    key::Key,
+    model::{KeyEvent, KeyEventType, KeyboardLayout},
+};
+
+// Fn is handled inside most keyboards and never reaches the OS
+const MODIFIERS: [Key; 8] = [
+    Key::LeftCtrl,
+    Key::LeftShift,
+    Key::LeftAlt,
+    Key::LeftSuper,
+    Key::RightAlt,
+    Key::RightSuper,
+    Key::RightShift,
+    Key::RightCtrl,
+];
+
+pub const ALPHAS: [Key; 26] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComboResult {
+    Registered,
+    // The step ended and the letter never arrived while the modifier was held
+    DroppedLetter,
+    // The letter arrived, but the modifier was released under it mid sweep
+    DroppedModifier,
+}
+
+// One step per modifier on the layout: hold it, sweep the alpha keys, press Return to move on
+pub struct ModifierMatrix {
+    pub modifiers: Vec<Key>,
+    pub step: usize,
+    modifier_held: bool,
+    sweep_started: bool,
+    pub results: HashMap<(Key, Key), ComboResult>,
+}
+
+#[derive(Serialize)]
+pub struct ModifierRow {
+    pub modifier: Key,
+    pub registered: Vec<Key>,
+    pub dropped_letter: Vec<Key>,
+    pub dropped_modifier: Vec<Key>,
+}
+
+impl ModifierMatrix {
+    pub fn new(layout: &KeyboardLayout) -> ModifierMatrix {
+        ModifierMatrix {
+            modifiers: MODIFIERS
+                .into_iter()
+                .filter(|modifier| layout.contains(modifier))
+                .collect(),
+            step: 0,
+            modifier_held: false,
+            sweep_started: false,
+            results: HashMap::new(),
+        }
+    }
+
+    pub fn current_modifier(&self) -> Option<Key> {
+        self.modifiers.get(self.step).copied()
+    }
+
+    pub fn record(&mut self, key_event: &KeyEventType) {
+        let Some(modifier) = self.current_modifier() else {
+            return;
+        };
+
+        match key_event {
+            KeyEventType::KeyPressed(KeyEvent { key: Some(key), .. }) if *key == modifier => {
+                self.modifier_held = true;
+                self.sweep_started = true;
+            }
+            KeyEventType::KeyReleased(KeyEvent { key: Some(key), .. }) if *key == modifier => {
+                self.modifier_held = false;
+            }
+            KeyEventType::KeyPressed(KeyEvent {
+                key: Some(Key::Return),
+                ..
+            }) => self.next_step(),
+            KeyEventType::KeyPressed(KeyEvent { key: Some(key), .. })
+                if ALPHAS.contains(key) && self.sweep_started =>
+            {
+                let result = if self.modifier_held {
+                    ComboResult::Registered
+                } else {
+                    ComboResult::DroppedModifier
+                };
+                // A combination that worked once stays registered
+                if self.results.get(&(modifier, *key)) != Some(&ComboResult::Registered) {
+                    self.results.insert((modifier, *key), result);
+                }
+                if ALPHAS
+                    .iter()
+                    .all(|alpha| self.results.contains_key(&(modifier, *alpha)))
+                {
+                    self.next_step();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn next_step(&mut self) {
+        if let Some(modifier) = self.current_modifier() {
+            for alpha in ALPHAS {
+                self.results
+                    .entry((modifier, alpha))
+                    .or_insert(ComboResult::DroppedLetter);
+            }
+        }
+        self.step += 1;
+        self.modifier_held = false;
+        self.sweep_started = false;
+    }
+
+    pub fn rows(&self) -> Vec<ModifierRow> {
+        self.modifiers
+            .iter()
+            .map(|modifier| {
+                let with_result = |expected: ComboResult| {
+                    ALPHAS
+                        .into_iter()
+                        .filter(|alpha| self.results.get(&(*modifier, *alpha)) == Some(&expected))
+                        .collect()
+                };
+                ModifierRow {
+                    modifier: *modifier,
+                    registered: with_result(ComboResult::Registered),
+                    dropped_letter: with_result(ComboResult::DroppedLetter),
+                    dropped_modifier: with_result(ComboResult::DroppedModifier),
+                }
+            })
+            .collect()
+    }
+}
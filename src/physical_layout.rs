@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use crate::{
+    ghosting::MatrixPosition,
+    key::Key,
+    keyboard100, keyboard60, keyboard80,
+    layout_file::LayoutFileKey,
+    model::{KeySize, KeyUI, VerticalKeyPart},
+};
+
+// 1u is 5 terminal cells, same as the built-in layouts
+const CELLS_PER_UNIT: f32 = 5.0;
+
+// How far, in cells, a key may sit from a built-in key it is guessed to be
+const MAX_GUESS_DISTANCE: u16 = 3;
+
+// A key as placed by QMK and KLE based formats, in key units from the top left corner
+#[derive(Deserialize)]
+pub struct PhysicalKey {
+    pub x: f32,
+    pub y: f32,
+    #[serde(default = "one_unit")]
+    pub w: f32,
+    #[serde(default = "one_unit")]
+    pub h: f32,
+    pub matrix: Option<MatrixPosition>,
+    pub label: Option<String>,
+}
+
+fn one_unit() -> f32 {
+    1.0
+}
+
+fn cells(units: f32) -> u16 {
+    (units * CELLS_PER_UNIT).round() as u16
+}
+
+// Keys whose `y` is less than half a unit apart share a row
+struct Rows {
+    ys: Vec<f32>,
+}
+
+impl Rows {
+    fn new(physical_keys: &[PhysicalKey]) -> Rows {
+        let mut ys: Vec<f32> = Vec::new();
+        for physical_key in physical_keys {
+            if !ys.iter().any(|y| (y - physical_key.y).abs() < 0.5) {
+                ys.push(physical_key.y);
+            }
+        }
+        ys.sort_by(f32::total_cmp);
+
+        Rows { ys }
+    }
+
+    fn of(&self, y: f32) -> Option<usize> {
+        self.ys.iter().position(|row_y| (row_y - y).abs() < 0.5)
+    }
+}
+
+struct PlacedKey {
+    x: f32,
+    w: f32,
+    key: Key,
+    part: Option<VerticalKeyPart>,
+    matrix: Option<MatrixPosition>,
+}
+
+impl PlacedKey {
+    fn new(physical_key: &PhysicalKey, key: Key) -> PlacedKey {
+        PlacedKey {
+            x: physical_key.x,
+            w: physical_key.w,
+            key,
+            part: None,
+            matrix: physical_key.matrix,
+        }
+    }
+}
+
+// Keys sharing a `y` form a kbt row, gaps turn into separators. Keys two units high are split into
+// a top part on their row and a bottom part on the next one.
+pub fn build_rows(physical_keys: &[PhysicalKey], keys: &[Key]) -> Vec<Vec<LayoutFileKey>> {
+    let rows = Rows::new(physical_keys);
+
+    let mut placed: Vec<Vec<PlacedKey>> = rows.ys.iter().map(|_| Vec::new()).collect();
+    for (physical_key, key) in physical_keys.iter().zip(keys) {
+        let Some(row) = rows.of(physical_key.y) else {
+            continue;
+        };
+        let below = rows
+            .of(physical_key.y + physical_key.h - 1.0)
+            .filter(|below| *below > row);
+        match below {
+            Some(below) => {
+                placed[row].push(PlacedKey {
+                    part: Some(VerticalKeyPart::Top),
+                    ..PlacedKey::new(physical_key, *key)
+                });
+                placed[below].push(PlacedKey {
+                    part: Some(VerticalKeyPart::Bottom),
+                    matrix: None,
+                    ..PlacedKey::new(physical_key, *key)
+                });
+            }
+            None => placed[row].push(PlacedKey::new(physical_key, *key)),
+        }
+    }
+
+    placed
+        .into_iter()
+        .map(|mut row| {
+            row.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+            let mut cursor: u16 = 0;
+            let mut keys: Vec<LayoutFileKey> = Vec::new();
+            for placed_key in row {
+                let start = cells(placed_key.x);
+                let end = cells(placed_key.x + placed_key.w);
+                if start > cursor {
+                    let (size, correction) = KeySize::fitting(start - cursor);
+                    keys.push(LayoutFileKey {
+                        key: Key::Separator,
+                        size,
+                        correction,
+                        part: None,
+                        matrix: None,
+                    });
+                }
+                let (size, correction) = KeySize::fitting(end.saturating_sub(start.max(cursor)));
+                keys.push(LayoutFileKey {
+                    key: placed_key.key,
+                    size,
+                    correction,
+                    part: placed_key.part,
+                    matrix: placed_key.matrix,
+                });
+                cursor = end.max(cursor);
+            }
+
+            keys
+        })
+        .collect()
+}
+
+// Without legends or a keymap, keys are bound to the key at the same spot of the closest built-in layout
+pub fn guess_keys(physical_keys: &[PhysicalKey]) -> Vec<Key> {
+    let rows = Rows::new(physical_keys);
+    let width = physical_keys
+        .iter()
+        .map(|physical_key| cells(physical_key.x + physical_key.w))
+        .max()
+        .unwrap_or(0);
+
+    let references: [Vec<Vec<(u16, Key)>>; 3] = [
+        key_centers(&keyboard60::ROWS),
+        key_centers(&keyboard80::ROWS),
+        key_centers(&keyboard100::ROWS),
+    ];
+    let Some(reference) = references.iter().min_by_key(|reference| {
+        let reference_width = reference
+            .iter()
+            .filter_map(|row| row.last().map(|(center, _)| center))
+            .max()
+            .copied()
+            .unwrap_or(0);
+        (
+            rows.ys.len().abs_diff(reference.len()),
+            width.abs_diff(reference_width),
+        )
+    }) else {
+        return Vec::new();
+    };
+
+    let mut used: HashSet<Key> = HashSet::new();
+    physical_keys
+        .iter()
+        .map(|physical_key| {
+            let center = cells(physical_key.x + physical_key.w / 2.0);
+            let key = rows
+                .of(physical_key.y)
+                .and_then(|row| reference.get(row))
+                .and_then(|reference_row| {
+                    reference_row
+                        .iter()
+                        .filter(|(_, key)| !used.contains(key))
+                        .min_by_key(|(reference_center, _)| reference_center.abs_diff(center))
+                        .filter(|(reference_center, _)| {
+                            reference_center.abs_diff(center) <= MAX_GUESS_DISTANCE
+                        })
+                })
+                .map_or(Key::Separator, |(_, key)| *key);
+            used.insert(key);
+            key
+        })
+        .collect()
+}
+
+fn key_centers(rows: &[&[KeyUI]]) -> Vec<Vec<(u16, Key)>> {
+    rows.iter()
+        .map(|row| {
+            let mut cursor: u16 = 0;
+            let mut centers: Vec<(u16, Key)> = Vec::new();
+            for (idx, ui_key) in row.iter().enumerate() {
+                let previous = idx.checked_sub(1).and_then(|idx| row.get(idx));
+                if VerticalKeyPart::is_stacked_below(previous, ui_key) {
+                    continue;
+                }
+                let len = u16::try_from(
+                    ui_key.size.static_len() as i16 + ui_key.size_correction.unwrap_or(0),
+                )
+                .unwrap_or(0);
+                if ui_key.key != Key::Separator
+                    && !matches!(ui_key.vertical_key_part, Some(VerticalKeyPart::Bottom))
+                {
+                    centers.push((cursor + len / 2, ui_key.key));
+                }
+                cursor += len;
+            }
+
+            centers
+        })
+        .collect()
+}
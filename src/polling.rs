@@ -0,0 +1,142 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+// Keyboards only send a report when something changes, so the polling rate can't be read off
+// directly. Events still land on the polling grid, every gap between them is a whole number of
+// polling intervals. The slowest common rate whose grid fits the gaps is the estimate.
+const CANDIDATE_RATES_HZ: [u32; 7] = [125, 250, 500, 1000, 2000, 4000, 8000];
+
+// Gaps are off the grid by a quarter of the interval on average when they don't follow it at all
+const GRID_FIT: f64 = 0.1;
+
+const MIN_SAMPLES: usize = 30;
+
+// Longer gaps are the user pausing, not rolling across keys
+const MAX_GAP: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+pub struct PollingStats {
+    last_event_at: Option<Instant>,
+    gaps: Vec<Duration>,
+}
+
+#[derive(Serialize)]
+pub struct PollingEstimate {
+    pub rate_hz: u32,
+    pub interval_ms: f64,
+    pub jitter_ms: f64,
+    pub samples: usize,
+}
+
+impl PollingStats {
+    pub fn record(&mut self, at: Instant) {
+        if let Some(last_event_at) = self.last_event_at {
+            let gap = at.saturating_duration_since(last_event_at);
+            // Zero gaps are events from the same report
+            if !gap.is_zero() && gap <= MAX_GAP {
+                self.gaps.push(gap);
+            }
+        }
+        self.last_event_at = Some(self.last_event_at.map_or(at, |last| last.max(at)));
+    }
+
+    pub fn samples(&self) -> usize {
+        self.gaps.len()
+    }
+
+    pub fn has_enough_samples(&self) -> bool {
+        self.samples() >= MIN_SAMPLES
+    }
+
+    pub fn estimate(&self) -> Option<PollingEstimate> {
+        if !self.has_enough_samples() {
+            return None;
+        }
+
+        let gaps: Vec<f64> = self.gaps.iter().map(Duration::as_secs_f64).collect();
+        let samples = gaps.len() as f64;
+
+        CANDIDATE_RATES_HZ.iter().find_map(|rate_hz| {
+            let period = 1.0 / f64::from(*rate_hz);
+            let ticks: Vec<f64> = gaps
+                .iter()
+                .map(|gap| (gap / period).round().max(1.0))
+                .collect();
+            let deviations: Vec<f64> = gaps
+                .iter()
+                .zip(&ticks)
+                .map(|(gap, ticks)| gap - ticks * period)
+                .collect();
+
+            let mean_deviation = deviations.iter().map(|d| d.abs()).sum::<f64>() / samples;
+            if mean_deviation / period > GRID_FIT {
+                return None;
+            }
+
+            let interval = gaps.iter().sum::<f64>() / ticks.iter().sum::<f64>();
+            let jitter = (deviations.iter().map(|d| d * d).sum::<f64>() / samples).sqrt();
+
+            Some(PollingEstimate {
+                rate_hz: *rate_hz,
+                interval_ms: interval * 1000.0,
+                jitter_ms: jitter * 1000.0,
+                samples: gaps.len(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Events at the given gaps, each one pushed early and late by turns
+    fn stats(gaps_us: &[u64], jitter_us: u64, count: usize) -> PollingStats {
+        let mut stats = PollingStats::default();
+        let mut at = Instant::now();
+        stats.record(at);
+        for (idx, gap_us) in gaps_us.iter().cycle().take(count).enumerate() {
+            let gap_us = if idx % 2 == 0 {
+                gap_us + jitter_us
+            } else {
+                gap_us - jitter_us
+            };
+            at += Duration::from_micros(gap_us);
+            stats.record(at);
+        }
+
+        stats
+    }
+
+    #[test]
+    fn estimates_a_1000_hz_board() {
+        let estimate = stats(&[1000, 2000, 3000, 4000, 5000], 50, 100)
+            .estimate()
+            .unwrap();
+
+        assert_eq!(estimate.rate_hz, 1000);
+        assert!((estimate.jitter_ms - 0.05).abs() < 0.001);
+        assert!((estimate.interval_ms - 1.0).abs() < 0.01);
+        assert_eq!(estimate.samples, 100);
+    }
+
+    #[test]
+    fn estimates_a_125_hz_board() {
+        let estimate = stats(&[8000, 16000, 24000], 300, 60).estimate().unwrap();
+
+        assert_eq!(estimate.rate_hz, 125);
+        assert!((estimate.jitter_ms - 0.3).abs() < 0.001);
+        assert!((estimate.interval_ms - 8.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn needs_enough_short_gaps() {
+        assert!(stats(&[1000], 0, MIN_SAMPLES - 1).estimate().is_none());
+        assert!(stats(&[1000], 0, MIN_SAMPLES).estimate().is_some());
+
+        let pauses = stats(&[MAX_GAP.as_micros() as u64 + 1000], 0, 2 * MIN_SAMPLES);
+        assert_eq!(pauses.samples(), 0);
+        assert!(pauses.estimate().is_none());
+    }
+}
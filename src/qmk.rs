@@ -0,0 +1,411 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    key::Key,
+    layout_file::LayoutFile,
+    model::{KbtError, Legends},
+    physical_layout::{self, PhysicalKey},
+};
+
+#[derive(Deserialize)]
+struct QmkInfo {
+    keyboard_name: Option<String>,
+    layouts: BTreeMap<String, QmkLayout>,
+}
+
+#[derive(Deserialize)]
+struct QmkLayout {
+    layout: Vec<PhysicalKey>,
+}
+
+#[derive(Deserialize)]
+struct QmkKeymap {
+    layout: Option<String>,
+    layers: Vec<Vec<String>>,
+}
+
+// Turns one layout macro of a QMK info.json or keyboard.json into a kbt layout. Keys are bound
+// through the base layer of a keymap.json when given, otherwise through the legends in `label`,
+// or by their position when there are no legends.
+pub fn import(
+    info_path: &Path,
+    layout_name: Option<&str>,
+    keymap_path: Option<&Path>,
+) -> Result<LayoutFile, KbtError> {
+    let info: QmkInfo = read_json(info_path, "QMK info")?;
+    let keymap: Option<QmkKeymap> = keymap_path
+        .map(|keymap_path| read_json(keymap_path, "QMK keymap"))
+        .transpose()?;
+
+    let layout_name = layout_name
+        .or(keymap.as_ref().and_then(|keymap| keymap.layout.as_deref()))
+        .map(String::from)
+        .or_else(|| default_layout_name(&info))
+        .ok_or(KbtError {
+            message: format!(
+                "{} has several layouts, pick one with --qmk-layout: {}",
+                info_path.display(),
+                info.layouts
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        })?;
+    let layout = info.layouts.get(&layout_name).ok_or(KbtError {
+        message: format!("{} has no layout {}", info_path.display(), layout_name),
+    })?;
+
+    let keys: Vec<Key> = match &keymap {
+        Some(keymap) => keymap
+            .layers
+            .first()
+            .map(|base_layer| {
+                layout
+                    .layout
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, _)| {
+                        base_layer
+                            .get(idx)
+                            .and_then(|keycode| keycode_key(keycode))
+                            .unwrap_or(Key::Separator)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        // Plenty of info.json files carry no legends at all
+        None if layout.layout.iter().all(|qmk_key| qmk_key.label.is_none()) => {
+            physical_layout::guess_keys(&layout.layout)
+        }
+        None => label_keys(&layout.layout),
+    };
+
+    let unbound = keys.iter().filter(|key| **key == Key::Separator).count();
+    if unbound > 0 {
+        log::warn!("{} QMK keys have no kbt key, they are left blank", unbound);
+    }
+
+    Ok(LayoutFile {
+        name: format!(
+            "{} ({})",
+            info.keyboard_name.as_deref().unwrap_or("QMK keyboard"),
+            layout_name
+        ),
+        legends: Legends::Standard,
+        rows: physical_layout::build_rows(&layout.layout, &keys),
+        unbound_keys: unbound,
+    })
+}
+
+// `kind` names the file in errors, e.g. "QMK info" or "VIA definition"
+pub fn read_json<T: for<'de> Deserialize<'de>>(path: &Path, kind: &str) -> Result<T, KbtError> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+
+    serde_json::from_str(&content)
+        .map_err(|err| KbtError::from(format!("Invalid {} file {}: {}", kind, path.display(), err)))
+}
+
+fn default_layout_name(info: &QmkInfo) -> Option<String> {
+    if info.layouts.len() == 1 || info.layouts.contains_key("LAYOUT") {
+        info.layouts
+            .keys()
+            .find(|name| *name == "LAYOUT")
+            .or(info.layouts.keys().next())
+            .cloned()
+    } else {
+        None
+    }
+}
+
+// Modifier legends don't say which side they are on, the board's half does
+fn label_keys(qmk_keys: &[PhysicalKey]) -> Vec<Key> {
+    let board_middle = qmk_keys
+        .iter()
+        .map(|qmk_key| qmk_key.x + qmk_key.w)
+        .fold(0.0, f32::max)
+        / 2.0;
+
+    let mut used: HashSet<Key> = HashSet::new();
+    qmk_keys
+        .iter()
+        .map(|qmk_key| {
+            let label = qmk_key.label.as_deref().unwrap_or_default();
+            let right_side = qmk_key.x > board_middle;
+            let key = label_key(label, right_side, &used).unwrap_or(Key::Separator);
+            used.insert(key);
+            key
+        })
+        .collect()
+}
+
+fn label_key(label: &str, right_side: bool, used: &HashSet<Key>) -> Option<Key> {
+    let label = label.trim().to_lowercase();
+    let sided = |left: Key, right: Key| Some(if right_side { right } else { left });
+
+    let key = match label.as_str() {
+        "shift" | "lshift" | "rshift" => sided(Key::LeftShift, Key::RightShift),
+        "ctrl" | "control" | "lctrl" | "rctrl" => sided(Key::LeftCtrl, Key::RightCtrl),
+        "alt" | "option" | "lalt" | "ralt" | "altgr" => sided(Key::LeftAlt, Key::RightAlt),
+        "gui" | "win" | "super" | "meta" | "lgui" | "rgui" | "cmd" | "command" => {
+            sided(Key::LeftSuper, Key::RightSuper)
+        }
+        "fn" => Some(Key::Fn),
+        "esc" | "escape" => Some(Key::Esc),
+        "tab" => Some(Key::Tab),
+        "caps lock" | "caps" | "capslock" => Some(Key::CapsLock),
+        "backspace" | "bksp" | "bspc" => Some(Key::Backspace),
+        "enter" | "return" | "ent" => Some(Key::Return),
+        "space" | "spacebar" | "spc" => Some(Key::Spacebar),
+        "menu" | "app" | "apps" => Some(Key::Menu),
+        "print screen" | "prtsc" | "psc" | "prt sc" | "print" => Some(Key::PrintScreen),
+        "scroll lock" | "scrlk" | "scroll" => Some(Key::ScrollLock),
+        "pause" | "break" | "pause break" => Some(Key::PauseBreak),
+        "insert" | "ins" => Some(Key::Insert),
+        "home" => Some(Key::Home),
+        "page up" | "pgup" | "pg up" => Some(Key::PgUp),
+        "delete" | "del" => Some(Key::Delete),
+        "end" => Some(Key::End),
+        "page down" | "pgdn" | "pg dn" => Some(Key::PgDown),
+        "up" | "↑" => Some(Key::ArrowUp),
+        "down" | "↓" => Some(Key::ArrowDown),
+        "left" | "←" => Some(Key::ArrowLeft),
+        "right" | "→" => Some(Key::ArrowRight),
+        "num lock" | "num" | "numlock" => Some(Key::NumLock),
+        "`" | "~" | "grave" => Some(Key::Grave),
+        "-" | "_" => Some(Key::Hyphen),
+        "=" | "+" => Some(Key::Equal),
+        "[" | "{" => Some(Key::LeftBracket),
+        "]" | "}" => Some(Key::RightBracket),
+        "\\" | "|" => Some(Key::Backslash),
+        ";" | ":" => Some(Key::SemiColon),
+        "'" | "\"" => Some(Key::Apostrophe),
+        "," | "<" => Some(Key::Comma),
+        "." | ">" => Some(Key::Period),
+        "/" | "?" => Some(Key::QuestionMark),
+        "!" => Some(Key::One),
+        "@" => Some(Key::Two),
+        "#" => Some(Key::Three),
+        "$" => Some(Key::Four),
+        "%" => Some(Key::Five),
+        "^" => Some(Key::Six),
+        "&" => Some(Key::Seven),
+        "*" => Some(Key::Eight),
+        "(" => Some(Key::Nine),
+        ")" => Some(Key::Zero),
+        function if function.len() > 1 && function.starts_with('f') => {
+            function[1..].parse::<u8>().ok().and_then(function_key)
+        }
+        single if single.chars().count() == 1 => single.chars().next().and_then(char_key),
+        _ => None,
+    }?;
+
+    // The second digit, slash, star, dash, plus, dot or enter on a board is on the numpad
+    if used.contains(&key) {
+        return numpad_label_key(&label);
+    }
+
+    Some(key)
+}
+
+fn char_key(c: char) -> Option<Key> {
+    match c {
+        '0' => Some(Key::Zero),
+        '1' => Some(Key::One),
+        '2' => Some(Key::Two),
+        '3' => Some(Key::Three),
+        '4' => Some(Key::Four),
+        '5' => Some(Key::Five),
+        '6' => Some(Key::Six),
+        '7' => Some(Key::Seven),
+        '8' => Some(Key::Eight),
+        '9' => Some(Key::Nine),
+        letter => letter_key(letter.to_ascii_uppercase()),
+    }
+}
+
+fn letter_key(letter: char) -> Option<Key> {
+    match letter {
+        'A' => Some(Key::A),
+        'B' => Some(Key::B),
+        'C' => Some(Key::C),
+        'D' => Some(Key::D),
+        'E' => Some(Key::E),
+        'F' => Some(Key::F),
+        'G' => Some(Key::G),
+        'H' => Some(Key::H),
+        'I' => Some(Key::I),
+        'J' => Some(Key::J),
+        'K' => Some(Key::K),
+        'L' => Some(Key::L),
+        'M' => Some(Key::M),
+        'N' => Some(Key::N),
+        'O' => Some(Key::O),
+        'P' => Some(Key::P),
+        'Q' => Some(Key::Q),
+        'R' => Some(Key::R),
+        'S' => Some(Key::S),
+        'T' => Some(Key::T),
+        'U' => Some(Key::U),
+        'V' => Some(Key::V),
+        'W' => Some(Key::W),
+        'X' => Some(Key::X),
+        'Y' => Some(Key::Y),
+        'Z' => Some(Key::Z),
+        _ => None,
+    }
+}
+
+fn function_key(number: u8) -> Option<Key> {
+    match number {
+        1 => Some(Key::F1),
+        2 => Some(Key::F2),
+        3 => Some(Key::F3),
+        4 => Some(Key::F4),
+        5 => Some(Key::F5),
+        6 => Some(Key::F6),
+        7 => Some(Key::F7),
+        8 => Some(Key::F8),
+        9 => Some(Key::F9),
+        10 => Some(Key::F10),
+        11 => Some(Key::F11),
+        12 => Some(Key::F12),
+        13 => Some(Key::F13),
+        14 => Some(Key::F14),
+        15 => Some(Key::F15),
+        16 => Some(Key::F16),
+        17 => Some(Key::F17),
+        18 => Some(Key::F18),
+        19 => Some(Key::F19),
+        20 => Some(Key::F20),
+        21 => Some(Key::F21),
+        22 => Some(Key::F22),
+        23 => Some(Key::F23),
+        24 => Some(Key::F24),
+        _ => None,
+    }
+}
+
+fn numpad_label_key(label: &str) -> Option<Key> {
+    match label {
+        "0" | "ins" => Some(Key::NumpadZero),
+        "1" | "end" => Some(Key::NumpadOne),
+        "2" => Some(Key::NumpadTwo),
+        "3" | "pgdn" => Some(Key::NumpadThree),
+        "4" => Some(Key::NumpadFour),
+        "5" => Some(Key::NumpadFive),
+        "6" => Some(Key::NumpadSix),
+        "7" | "home" => Some(Key::NumpadSeven),
+        "8" => Some(Key::NumpadEight),
+        "9" | "pgup" => Some(Key::NumpadNine),
+        "/" => Some(Key::Div),
+        "*" => Some(Key::Mul),
+        "-" => Some(Key::Minus),
+        "+" => Some(Key::Plus),
+        "." | "del" => Some(Key::Decimal),
+        "enter" | "ent" | "return" => Some(Key::NumpadEnter),
+        _ => None,
+    }
+}
+
+// Base layer keycodes, layer-tap and mod-tap keys count as the key they send when tapped
+pub fn keycode_key(keycode: &str) -> Option<Key> {
+    let keycode = keycode
+        .rsplit(['(', ',', ' '])
+        .next()
+        .unwrap_or(keycode)
+        .trim_end_matches(')');
+
+    let name = keycode.strip_prefix("KC_")?;
+    let key = match name {
+        "ESC" | "ESCAPE" => Key::Esc,
+        "ENT" | "ENTER" => Key::Return,
+        "BSPC" | "BACKSPACE" => Key::Backspace,
+        "TAB" => Key::Tab,
+        "SPC" | "SPACE" => Key::Spacebar,
+        "MINS" | "MINUS" => Key::Hyphen,
+        "EQL" | "EQUAL" => Key::Equal,
+        "LBRC" | "LEFT_BRACKET" => Key::LeftBracket,
+        "RBRC" | "RIGHT_BRACKET" => Key::RightBracket,
+        "BSLS" | "BACKSLASH" | "NUHS" | "NONUS_HASH" => Key::Backslash,
+        "SCLN" | "SEMICOLON" => Key::SemiColon,
+        "QUOT" | "QUOTE" => Key::Apostrophe,
+        "GRV" | "GRAVE" => Key::Grave,
+        "COMM" | "COMMA" => Key::Comma,
+        "DOT" => Key::Period,
+        "SLSH" | "SLASH" => Key::QuestionMark,
+        "NUBS" | "NONUS_BACKSLASH" => Key::IntlBackslash,
+        "CAPS" | "CAPS_LOCK" => Key::CapsLock,
+        "PSCR" | "PRINT_SCREEN" => Key::PrintScreen,
+        "SCRL" | "SCROLL_LOCK" => Key::ScrollLock,
+        "PAUS" | "PAUSE" | "BRK" => Key::PauseBreak,
+        "INS" | "INSERT" => Key::Insert,
+        "HOME" => Key::Home,
+        "PGUP" | "PAGE_UP" => Key::PgUp,
+        "DEL" | "DELETE" => Key::Delete,
+        "END" => Key::End,
+        "PGDN" | "PAGE_DOWN" => Key::PgDown,
+        "RGHT" | "RIGHT" => Key::ArrowRight,
+        "LEFT" => Key::ArrowLeft,
+        "DOWN" => Key::ArrowDown,
+        "UP" => Key::ArrowUp,
+        "NUM" | "NUM_LOCK" => Key::NumLock,
+        "PSLS" | "KP_SLASH" => Key::Div,
+        "PAST" | "KP_ASTERISK" => Key::Mul,
+        "PMNS" | "KP_MINUS" => Key::Minus,
+        "PPLS" | "KP_PLUS" => Key::Plus,
+        "PENT" | "KP_ENTER" => Key::NumpadEnter,
+        "PDOT" | "KP_DOT" => Key::Decimal,
+        "PCMM" | "KP_COMMA" => Key::NumpadComma,
+        "P0" | "KP_0" => Key::NumpadZero,
+        "P1" | "KP_1" => Key::NumpadOne,
+        "P2" | "KP_2" => Key::NumpadTwo,
+        "P3" | "KP_3" => Key::NumpadThree,
+        "P4" | "KP_4" => Key::NumpadFour,
+        "P5" | "KP_5" => Key::NumpadFive,
+        "P6" | "KP_6" => Key::NumpadSix,
+        "P7" | "KP_7" => Key::NumpadSeven,
+        "P8" | "KP_8" => Key::NumpadEight,
+        "P9" | "KP_9" => Key::NumpadNine,
+        "LCTL" | "LEFT_CTRL" => Key::LeftCtrl,
+        "LSFT" | "LEFT_SHIFT" => Key::LeftShift,
+        "LALT" | "LEFT_ALT" | "LOPT" => Key::LeftAlt,
+        "LGUI" | "LEFT_GUI" | "LCMD" | "LWIN" => Key::LeftSuper,
+        "RCTL" | "RIGHT_CTRL" => Key::RightCtrl,
+        "RSFT" | "RIGHT_SHIFT" => Key::RightShift,
+        "RALT" | "RIGHT_ALT" | "ROPT" | "ALGR" => Key::RightAlt,
+        "RGUI" | "RIGHT_GUI" | "RCMD" | "RWIN" => Key::RightSuper,
+        "APP" | "APPLICATION" => Key::Menu,
+        "INT1" | "INTERNATIONAL_1" => Key::Ro,
+        "INT2" | "INTERNATIONAL_2" => Key::Kana,
+        "INT3" | "INTERNATIONAL_3" => Key::Yen,
+        "INT4" | "INTERNATIONAL_4" => Key::Henkan,
+        "INT5" | "INTERNATIONAL_5" => Key::Muhenkan,
+        "LNG1" | "LANGUAGE_1" => Key::Hangul,
+        "LNG2" | "LANGUAGE_2" => Key::Hanja,
+        "MUTE" | "AUDIO_MUTE" => Key::Mute,
+        "VOLU" | "AUDIO_VOL_UP" => Key::VolumeUp,
+        "VOLD" | "AUDIO_VOL_DOWN" => Key::VolumeDown,
+        "MPRV" | "MEDIA_PREV_TRACK" => Key::PrevTrack,
+        "MPLY" | "MEDIA_PLAY_PAUSE" => Key::PlayPause,
+        "MNXT" | "MEDIA_NEXT_TRACK" => Key::NextTrack,
+        "BRIU" | "BRIGHTNESS_UP" => Key::BrightnessUp,
+        "BRID" | "BRIGHTNESS_DOWN" => Key::BrightnessDown,
+        "CALC" | "CALCULATOR" => Key::Calculator,
+        "MAIL" => Key::Mail,
+        function if function.len() > 1 && function.starts_with('F') => {
+            function[1..].parse::<u8>().ok().and_then(function_key)?
+        }
+        single if single.len() == 1 => single.chars().next().and_then(char_key)?,
+        _ => return None,
+    };
+
+    Some(key)
+}
@@ -0,0 +1,112 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    key::Key,
+    leds::{LedCheck, LockKey},
+    model::{App, KbtError, KeyBackend, KeyState, StuckKey},
+    modifier_matrix::{ModifierMatrix, ModifierRow},
+    polling::PollingEstimate,
+};
+
+#[derive(Serialize)]
+pub struct Report {
+    pub kbt_version: &'static str,
+    pub layout: String,
+    pub device: Option<String>,
+    pub serial: Option<String>,
+    pub backend: KeyBackend,
+    pub duration_secs: u64,
+    pub tested_keys: Vec<Key>,
+    pub untested_keys: Vec<Key>,
+    pub extra_keys: Vec<String>,
+    pub polling: Option<PollingEstimate>,
+    pub stuck_keys: Vec<Key>,
+    pub lost_releases: Vec<Key>,
+    pub chatter_keys: Vec<Key>,
+    pub led_checks: BTreeMap<LockKey, LedCheck>,
+    pub modifier_matrix: Option<Vec<ModifierRow>>,
+    pub ghost_keys: Vec<Key>,
+    pub compare: Option<CompareReport>,
+}
+
+#[derive(Serialize)]
+pub struct CompareReport {
+    pub device: String,
+    pub tested_keys: Vec<Key>,
+    pub differing_keys: Vec<Key>,
+}
+
+impl Report {
+    pub fn new(state: &App) -> Report {
+        let (tested_keys, untested_keys): (Vec<Key>, Vec<Key>) =
+            state.layout.keys().into_iter().partition(|key| {
+                matches!(
+                    state.key_states.get(key),
+                    Some(KeyState::Pressed | KeyState::Released)
+                )
+            });
+
+        Report {
+            kbt_version: env!("CARGO_PKG_VERSION"),
+            layout: state.layout.name.clone(),
+            device: state.device.as_ref().map(|device| device.to_string()),
+            serial: state.serial.clone(),
+            backend: state.backend,
+            duration_secs: state.started_at.elapsed().as_secs(),
+            tested_keys,
+            untested_keys,
+            extra_keys: state
+                .extra_keys()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect(),
+            polling: state.polling.estimate(),
+            stuck_keys: sorted_keys(
+                state
+                    .stuck_keys
+                    .iter()
+                    .filter(|(_, stuck_key)| matches!(stuck_key, StuckKey::Held))
+                    .map(|(key, _)| *key),
+            ),
+            lost_releases: sorted_keys(state.lost_releases.iter().copied()),
+            chatter_keys: sorted_keys(state.key_timings.chatter.keys().copied()),
+            led_checks: state
+                .led_test
+                .results
+                .iter()
+                .map(|(lock_key, led_check)| (*lock_key, *led_check))
+                .collect(),
+            ghost_keys: sorted_keys(state.ghost_keys.iter().copied()),
+            modifier_matrix: state.modifier_matrix.as_ref().map(ModifierMatrix::rows),
+            compare: state.compare.as_ref().map(|board| CompareReport {
+                device: board.device.to_string(),
+                tested_keys: state
+                    .layout
+                    .keys()
+                    .into_iter()
+                    .filter(|key| {
+                        matches!(
+                            board.key_states.get(key),
+                            Some(KeyState::Pressed | KeyState::Released)
+                        )
+                    })
+                    .collect(),
+                differing_keys: state.differing_keys(),
+            }),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), KbtError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn sorted_keys(keys: impl Iterator<Item = Key>) -> Vec<Key> {
+    let mut keys: Vec<Key> = keys.collect();
+    keys.sort_by_key(|key| format!("{:?}", key));
+
+    keys
+}
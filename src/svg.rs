@@ -0,0 +1,224 @@
+use std::{collections::HashMap, fmt::Write, fs, path::Path};
+
+use crate::{
+    key::Key,
+    locale::{self, Locale},
+    model::{App, KbtError, KeyState, KeyboardLayout, VerticalKeyPart},
+};
+
+// One terminal cell is 0.2u, so a 1u key is 50px wide and a row 50px tall
+const CELL_PX: u32 = 10;
+const ROW_PX: u32 = 50;
+const KEY_GAP_PX: u32 = 2;
+const MARGIN_PX: u32 = 20;
+const TITLE_PX: u32 = 30;
+const LEGEND_PX: u32 = 30;
+
+#[derive(Clone, Copy)]
+pub enum KeyMark {
+    Untested,
+    Tested,
+    Chatter,
+    Stuck,
+}
+
+impl KeyMark {
+    const ALL: [KeyMark; 4] = [
+        KeyMark::Untested,
+        KeyMark::Tested,
+        KeyMark::Chatter,
+        KeyMark::Stuck,
+    ];
+
+    fn fill(&self) -> &'static str {
+        match self {
+            KeyMark::Untested => "#ffffff",
+            KeyMark::Tested => "#b7e4a5",
+            KeyMark::Chatter => "#ffcc66",
+            KeyMark::Stuck => "#ff7b7b",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyMark::Untested => "untested",
+            KeyMark::Tested => "tested",
+            KeyMark::Chatter => "chatter",
+            KeyMark::Stuck => "stuck",
+        }
+    }
+}
+
+// Worst thing that happened to each key of the session, stuck beats chatter beats tested
+pub fn key_marks(state: &App) -> HashMap<Key, KeyMark> {
+    state
+        .layout
+        .keys()
+        .into_iter()
+        .map(|key| {
+            let mark = if state.stuck_keys.contains_key(&key) || state.lost_releases.contains(&key)
+            {
+                KeyMark::Stuck
+            } else if state.key_timings.chatter.contains_key(&key) {
+                KeyMark::Chatter
+            } else if let Some(KeyState::Pressed | KeyState::Released) = state.key_states.get(&key)
+            {
+                KeyMark::Tested
+            } else {
+                KeyMark::Untested
+            };
+            (key, mark)
+        })
+        .collect()
+}
+
+pub fn write(
+    path: &Path,
+    layout: &KeyboardLayout,
+    locale: Option<Locale>,
+    marks: Option<&HashMap<Key, KeyMark>>,
+) -> Result<(), KbtError> {
+    fs::write(path, render(layout, locale, marks))
+        .map_err(|err| KbtError::from(format!("Failed to write {}: {}", path.display(), err)))
+}
+
+// Plain layout preview without marks, test results with them
+pub fn render(
+    layout: &KeyboardLayout,
+    locale: Option<Locale>,
+    marks: Option<&HashMap<Key, KeyMark>>,
+) -> String {
+    let board_width = u32::from(layout.width) * CELL_PX;
+    let board_height = layout.rows.len() as u32 * ROW_PX;
+    let width = board_width + 2 * MARGIN_PX;
+    let height =
+        TITLE_PX + board_height + 2 * MARGIN_PX + if marks.is_some() { LEGEND_PX } else { 0 };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="sans-serif">"#
+    );
+    let _ = writeln!(
+        svg,
+        r##"<rect width="{width}" height="{height}" fill="#f4f4f4"/>"##
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{MARGIN_PX}" y="{}" font-size="16">{}</text>"#,
+        MARGIN_PX + 10,
+        escape(&layout.name)
+    );
+
+    let board_top = MARGIN_PX + TITLE_PX;
+    for (row_idx, row) in layout.rows.iter().enumerate() {
+        let y = board_top + row_idx as u32 * ROW_PX;
+        let mut x = MARGIN_PX;
+        let mut slot_x = x;
+
+        for (x_pos, ui_key) in row.keys.iter().enumerate() {
+            let previous = x_pos.checked_sub(1).and_then(|idx| row.keys.get(idx));
+            let key_width = (ui_key.size.static_len() as i16 + ui_key.size_correction.unwrap_or(0))
+                .max(0) as u32
+                * CELL_PX;
+            if !VerticalKeyPart::is_stacked_below(previous, ui_key) {
+                slot_x = x;
+                x += key_width;
+            }
+
+            if ui_key.key == Key::Separator {
+                continue;
+            }
+
+            let mark = marks
+                .and_then(|marks| marks.get(&ui_key.key))
+                .copied()
+                .unwrap_or(KeyMark::Untested);
+            let (left, right) = (slot_x + KEY_GAP_PX, slot_x + key_width - KEY_GAP_PX);
+            let (top, bottom) = (y + KEY_GAP_PX, y + ROW_PX - KEY_GAP_PX);
+
+            // Parts of a two row key leave the edge they share open, like the terminal board
+            let (shape, legend_y, font_size) = match ui_key.vertical_key_part {
+                Some(VerticalKeyPart::Top) if row_idx + 1 < layout.rows.len() => (
+                    format!("M{left},{} V{top} H{right} V{}", y + ROW_PX, y + ROW_PX),
+                    Some(y + ROW_PX / 2),
+                    12,
+                ),
+                Some(VerticalKeyPart::Bottom) => {
+                    (format!("M{left},{y} V{bottom} H{right} V{y}"), None, 12)
+                }
+                Some(VerticalKeyPart::UpperHalf) => (
+                    rounded_rect(left, top, right, y + ROW_PX / 2 - 1),
+                    Some(y + ROW_PX / 4 + 1),
+                    9,
+                ),
+                Some(VerticalKeyPart::LowerHalf) => (
+                    rounded_rect(left, y + ROW_PX / 2 + 1, right, bottom),
+                    Some(y + ROW_PX * 3 / 4 - 1),
+                    9,
+                ),
+                _ => (
+                    rounded_rect(left, top, right, bottom),
+                    Some(y + ROW_PX / 2),
+                    12,
+                ),
+            };
+
+            let legend = legend_y
+                .map(|legend_y| {
+                    format!(
+                        r#"<text x="{}" y="{legend_y}" font-size="{font_size}" text-anchor="middle" dominant-baseline="middle">{}</text>"#,
+                        slot_x + key_width / 2,
+                        escape(&locale::legend(ui_key.key, layout.legends, locale))
+                    )
+                })
+                .unwrap_or_default();
+            let key_name = format!("{:?}", ui_key.key);
+            let _ = writeln!(
+                svg,
+                r##"<g data-key="{key_name}"><title>{key_name}</title><path d="{shape}" fill="{}" stroke="#444"/>{legend}</g>"##,
+                mark.fill(),
+            );
+        }
+    }
+
+    if marks.is_some() {
+        let y = board_top + board_height + MARGIN_PX;
+        for (idx, mark) in KeyMark::ALL.iter().enumerate() {
+            let x = MARGIN_PX + idx as u32 * 100;
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{x}" y="{y}" width="14" height="14" rx="2" fill="{}" stroke="#444"/><text x="{}" y="{}" font-size="12">{}</text>"##,
+                mark.fill(),
+                x + 20,
+                y + 11,
+                mark.label()
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn rounded_rect(left: u32, top: u32, right: u32, bottom: u32) -> String {
+    format!(
+        "M{},{top} H{} Q{right},{top} {right},{} V{} Q{right},{bottom} {},{bottom} H{} Q{left},{bottom} {left},{} V{} Q{left},{top} {},{top} Z",
+        left + 3,
+        right - 3,
+        top + 3,
+        bottom - 3,
+        right - 3,
+        left + 3,
+        bottom - 3,
+        top + 3,
+        left + 3,
+    )
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,380 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    ghosting::MatrixPosition,
+    key::Key,
+    layout_file::LayoutFile,
+    model::{KbtError, Legends},
+    physical_layout::{self, PhysicalKey},
+    qmk,
+};
+
+// VIA and Vial definitions share this shape, Vial only adds fields kbt doesn't need
+#[derive(Deserialize)]
+struct ViaDefinition {
+    name: String,
+    matrix: ViaMatrix,
+    layouts: ViaLayouts,
+}
+
+#[derive(Deserialize)]
+struct ViaMatrix {
+    cols: u16,
+}
+
+#[derive(Deserialize)]
+struct ViaLayouts {
+    #[serde(default)]
+    labels: Vec<Value>,
+    keymap: Vec<Vec<Value>>,
+}
+
+// A key of the KLE keymap, with the layout option it belongs to
+struct ViaKey {
+    physical_key: PhysicalKey,
+    option: Option<(usize, usize)>,
+}
+
+// Turns a VIA/Vial definition into a kbt layout. `options` pick the layout options, e.g. "Split
+// Backspace=on" or "1=2", unset options keep their first choice. A saved VIA keymap or a Vial .vil
+// binds the keys, otherwise they are guessed from their position.
+pub fn import(
+    definition_path: &Path,
+    options: &[String],
+    keymap_path: Option<&Path>,
+) -> Result<LayoutFile, KbtError> {
+    let definition: ViaDefinition = qmk::read_json(definition_path, "VIA definition")?;
+    let choices = resolve_options(&definition.layouts.labels, options)?;
+
+    let via_keys = parse_keymap(&definition.layouts.keymap);
+    let physical_keys = select_options(via_keys, &choices);
+
+    let keys: Vec<Key> = match keymap_path {
+        Some(keymap_path) => {
+            let keycodes = read_keycodes(keymap_path, definition.matrix.cols)?;
+            physical_keys
+                .iter()
+                .map(|physical_key| {
+                    physical_key
+                        .matrix
+                        .and_then(|position| keycodes.get(&position))
+                        .and_then(|keycode| qmk::keycode_key(keycode))
+                        .unwrap_or(Key::Separator)
+                })
+                .collect()
+        }
+        None => physical_layout::guess_keys(&physical_keys),
+    };
+
+    Ok(LayoutFile {
+        name: definition.name,
+        legends: Legends::Standard,
+        rows: physical_layout::build_rows(&physical_keys, &keys),
+        unbound_keys: keys.iter().filter(|key| **key == Key::Separator).count(),
+    })
+}
+
+// Labels are either a plain name for an on/off option or [name, first choice, second choice, ...]
+fn resolve_options(
+    labels: &[Value],
+    options: &[String],
+) -> Result<HashMap<usize, usize>, KbtError> {
+    let mut choices: HashMap<usize, usize> = HashMap::new();
+
+    for option in options {
+        let (name, choice) = option.split_once('=').ok_or(KbtError {
+            message: format!("Layout option {} should look like NAME=CHOICE", option),
+        })?;
+
+        let (index, label) = name
+            .parse::<usize>()
+            .ok()
+            .map(|index| (index, labels.get(index)))
+            .or_else(|| {
+                labels
+                    .iter()
+                    .enumerate()
+                    .find(|(_, label)| label_name(label).eq_ignore_ascii_case(name.trim()))
+                    .map(|(index, label)| (index, Some(label)))
+            })
+            .ok_or(KbtError {
+                message: format!("Unknown layout option {}", name),
+            })?;
+
+        let choice_names: Vec<&str> = match label.and_then(Value::as_array) {
+            Some(label) => label.iter().skip(1).filter_map(Value::as_str).collect(),
+            None => vec!["off", "on"],
+        };
+        let choice = choice
+            .parse::<usize>()
+            .ok()
+            .or_else(|| {
+                choice_names
+                    .iter()
+                    .position(|choice_name| choice_name.eq_ignore_ascii_case(choice.trim()))
+            })
+            .or(match choice.trim() {
+                "false" | "no" => Some(0),
+                "true" | "yes" => Some(1),
+                _ => None,
+            })
+            .ok_or(KbtError {
+                message: format!(
+                    "Unknown choice {} for layout option {}, pick one of {}",
+                    choice,
+                    name,
+                    choice_names.join(", ")
+                ),
+            })?;
+
+        choices.insert(index, choice);
+    }
+
+    Ok(choices)
+}
+
+fn label_name(label: &Value) -> &str {
+    match label {
+        Value::Array(label) => label.first().and_then(Value::as_str).unwrap_or_default(),
+        label => label.as_str().unwrap_or_default(),
+    }
+}
+
+// KLE rows: objects change the position or size of the next key, strings are keys. VIA puts the
+// matrix position in the top left legend and the layout option in the bottom right one.
+fn parse_keymap(keymap: &[Vec<Value>]) -> Vec<ViaKey> {
+    let mut via_keys: Vec<ViaKey> = Vec::new();
+    let mut y: f32 = 0.0;
+
+    for row in keymap {
+        let mut x: f32 = 0.0;
+        let (mut w, mut h): (f32, f32) = (1.0, 1.0);
+
+        for item in row {
+            match item {
+                Value::Object(properties) => {
+                    let number = |name: &str| properties.get(name).and_then(Value::as_f64);
+                    x += number("x").unwrap_or(0.0) as f32;
+                    y += number("y").unwrap_or(0.0) as f32;
+                    w = number("w").map_or(w, |w| w as f32);
+                    h = number("h").map_or(h, |h| h as f32);
+                }
+                Value::String(legends) => {
+                    let legends: Vec<&str> = legends.split('\n').collect();
+                    via_keys.push(ViaKey {
+                        physical_key: PhysicalKey {
+                            x,
+                            y,
+                            w,
+                            h,
+                            matrix: legends.first().and_then(|legend| pair(legend)),
+                            label: None,
+                        },
+                        option: legends
+                            .get(3)
+                            .and_then(|legend| pair(legend))
+                            .map(|(option, choice)| (usize::from(option), usize::from(choice))),
+                    });
+                    x += w;
+                    (w, h) = (1.0, 1.0);
+                }
+                _ => {}
+            }
+        }
+
+        y += 1.0;
+    }
+
+    via_keys
+}
+
+fn pair(legend: &str) -> Option<MatrixPosition> {
+    let (a, b) = legend.split_once(',')?;
+    Some((a.trim().parse().ok()?, b.trim().parse().ok()?))
+}
+
+// VIA draws every choice of an option apart from the board, the chosen one is moved onto the spot
+// of the first choice
+fn select_options(via_keys: Vec<ViaKey>, choices: &HashMap<usize, usize>) -> Vec<PhysicalKey> {
+    let origin = |option: usize, choice: usize| {
+        via_keys
+            .iter()
+            .filter(|via_key| via_key.option == Some((option, choice)))
+            .fold(None, |origin: Option<(f32, f32)>, via_key| {
+                let (x, y) = (via_key.physical_key.x, via_key.physical_key.y);
+                Some(origin.map_or((x, y), |(ox, oy)| (ox.min(x), oy.min(y))))
+            })
+    };
+
+    let offsets: HashMap<usize, (f32, f32)> = choices
+        .iter()
+        .filter_map(|(option, choice)| {
+            let (x0, y0) = origin(*option, 0)?;
+            let (x, y) = origin(*option, *choice)?;
+            Some((*option, (x0 - x, y0 - y)))
+        })
+        .collect();
+
+    via_keys
+        .into_iter()
+        .filter_map(|via_key| match via_key.option {
+            None => Some(via_key.physical_key),
+            Some((option, choice)) if choice == choices.get(&option).copied().unwrap_or(0) => {
+                let (dx, dy) = offsets.get(&option).copied().unwrap_or((0.0, 0.0));
+                Some(PhysicalKey {
+                    x: via_key.physical_key.x + dx,
+                    y: via_key.physical_key.y + dy,
+                    ..via_key.physical_key
+                })
+            }
+            Some(_) => None,
+        })
+        .collect()
+}
+
+// Base layer keycodes by matrix position, from a saved VIA keymap (`layers` in matrix order) or a
+// Vial .vil (`layout` as layers of rows of columns)
+fn read_keycodes(path: &Path, cols: u16) -> Result<HashMap<MatrixPosition, String>, KbtError> {
+    let keymap: Value = qmk::read_json(path, "VIA keymap")?;
+
+    let keycodes: HashMap<MatrixPosition, String> =
+        if let Some(layer) = keymap["layers"].get(0).and_then(Value::as_array) {
+            layer
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, keycode)| {
+                    let row = u8::try_from(idx / usize::from(cols.max(1))).ok()?;
+                    let col = u8::try_from(idx % usize::from(cols.max(1))).ok()?;
+                    Some(((row, col), keycode.as_str()?.to_string()))
+                })
+                .collect()
+        } else if let Some(rows) = keymap["layout"].get(0).and_then(Value::as_array) {
+            rows.iter()
+                .enumerate()
+                .flat_map(|(row, columns)| {
+                    columns
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .enumerate()
+                        .filter_map(move |(col, keycode)| {
+                            Some((
+                                (u8::try_from(row).ok()?, u8::try_from(col).ok()?),
+                                keycode.as_str()?.to_string(),
+                            ))
+                        })
+                })
+                .collect()
+        } else {
+            return Err(KbtError {
+                message: format!("{} is neither a VIA keymap nor a Vial .vil", path.display()),
+            });
+        };
+
+    Ok(keycodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two rows, the 2u backspace can be split into two keys
+    const KEYMAP: &str = r#"[
+        ["0,0", "0,1", {"w": 2}, "0,2\n\n\n0,0", {"x": 1}, "0,2\n\n\n0,1", "0,3\n\n\n0,1"],
+        [{"w": 1.5}, "1,0", {"y": 0.25}, "1,1"]
+    ]"#;
+
+    fn keymap() -> Vec<Vec<Value>> {
+        serde_json::from_str(KEYMAP).unwrap()
+    }
+
+    fn positions(physical_keys: &[PhysicalKey]) -> Vec<(f32, f32, f32, Option<MatrixPosition>)> {
+        physical_keys
+            .iter()
+            .map(|key| (key.x, key.y, key.w, key.matrix))
+            .collect()
+    }
+
+    #[test]
+    fn pairs_legends() {
+        assert_eq!(pair("1,2"), Some((1, 2)));
+        assert_eq!(pair(" 3 , 14 "), Some((3, 14)));
+        assert_eq!(pair("Esc"), None);
+        assert_eq!(pair("1,x"), None);
+    }
+
+    #[test]
+    fn parses_positions_sizes_and_options() {
+        let via_keys = parse_keymap(&keymap());
+
+        let options: Vec<Option<(usize, usize)>> =
+            via_keys.iter().map(|via_key| via_key.option).collect();
+        let physical_keys: Vec<PhysicalKey> = via_keys
+            .into_iter()
+            .map(|via_key| via_key.physical_key)
+            .collect();
+
+        assert_eq!(
+            positions(&physical_keys),
+            vec![
+                (0.0, 0.0, 1.0, Some((0, 0))),
+                (1.0, 0.0, 1.0, Some((0, 1))),
+                (2.0, 0.0, 2.0, Some((0, 2))),
+                (5.0, 0.0, 1.0, Some((0, 2))),
+                (6.0, 0.0, 1.0, Some((0, 3))),
+                (0.0, 1.0, 1.5, Some((1, 0))),
+                (1.5, 1.25, 1.0, Some((1, 1))),
+            ]
+        );
+        assert_eq!(
+            options,
+            vec![
+                None,
+                None,
+                Some((0, 0)),
+                Some((0, 1)),
+                Some((0, 1)),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_first_choice_by_default() {
+        let physical_keys = select_options(parse_keymap(&keymap()), &HashMap::new());
+
+        assert_eq!(
+            positions(&physical_keys),
+            vec![
+                (0.0, 0.0, 1.0, Some((0, 0))),
+                (1.0, 0.0, 1.0, Some((0, 1))),
+                (2.0, 0.0, 2.0, Some((0, 2))),
+                (0.0, 1.0, 1.5, Some((1, 0))),
+                (1.5, 1.25, 1.0, Some((1, 1))),
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_the_chosen_option_onto_the_board() {
+        let labels: Vec<Value> = vec![Value::from("Split Backspace")];
+        let choices = resolve_options(&labels, &[String::from("Split Backspace=on")]).unwrap();
+        let physical_keys = select_options(parse_keymap(&keymap()), &choices);
+
+        assert_eq!(
+            positions(&physical_keys),
+            vec![
+                (0.0, 0.0, 1.0, Some((0, 0))),
+                (1.0, 0.0, 1.0, Some((0, 1))),
+                (2.0, 0.0, 1.0, Some((0, 2))),
+                (3.0, 0.0, 1.0, Some((0, 3))),
+                (0.0, 1.0, 1.5, Some((1, 0))),
+                (1.5, 1.25, 1.0, Some((1, 1))),
+            ]
+        );
+    }
+}
@@ -1,25 +1,146 @@
+use std::collections::HashMap;
+
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, BorderType, Borders, Paragraph};
 use ratatui::Frame;
 
+use crate::batch::Batch;
+use crate::editor::LayoutEditor;
+use crate::ghosting::{self, MatrixPosition};
 use crate::key::Key;
-use crate::model::{KbtError, KeyState, KeyUI, VerticalKeyPart};
+use crate::learn_layout::LayoutLearner;
+use crate::leds::{LedCheck, LockKey, LockLeds};
+use crate::locale::{self, Locale};
+use crate::model::{
+    KbtError, KeyEventType, KeyState, KeyTimings, KeyUI, KeyboardLayout, StuckKey, TestMode,
+    VerticalKeyPart,
+};
+use crate::modifier_matrix::{ComboResult, ModifierMatrix, ALPHAS};
 use crate::{App, KEY_HEIGHT};
 
+pub enum BoardArrangement {
+    Single,
+    SideBySide,
+    Stacked,
+}
+
+// Gap between compared boards, leaves room for the device header of the second one
+const BOARD_GAP: u16 = 3;
+
+pub fn arrange_boards(terminal_size: Rect, state: &App) -> Option<BoardArrangement> {
+    let layout = &state.layout;
+    let fits =
+        |width: u16, height: u16| terminal_size.width > width && terminal_size.height > height;
+
+    match state.compare {
+        None if fits(layout.width, layout.height) => Some(BoardArrangement::Single),
+        Some(_) if fits(layout.width * 2 + BOARD_GAP, layout.height) => {
+            Some(BoardArrangement::SideBySide)
+        }
+        Some(_) if fits(layout.width, layout.height * 2 + BOARD_GAP) => {
+            Some(BoardArrangement::Stacked)
+        }
+        _ => None,
+    }
+}
+
 pub fn draw(frame: &mut Frame, state: &App) -> Result<(), KbtError> {
+    draw_unbound_keys(&state.layout, frame);
+
+    if let Some(matrix) = &state.modifier_matrix {
+        return draw_modifier_matrix(matrix, state, frame);
+    }
+    if let TestMode::Matrix = state.mode {
+        return draw_switch_matrix(state, frame);
+    }
+
     let layout = &state.layout;
     let terminal_size: Rect = frame.size();
 
-    let left_padding: u16 = (terminal_size.width / 2) - (layout.width / 2);
-    let top_padding: u16 = (terminal_size.height / 2) - (layout.height / 2);
+    let arrangement = arrange_boards(terminal_size, state).ok_or(KbtError {
+        message: String::from("Layout does not fit the terminal"),
+    })?;
+    let (boards_width, boards_height) = match arrangement {
+        BoardArrangement::Single => (layout.width, layout.height),
+        BoardArrangement::SideBySide => (layout.width * 2 + BOARD_GAP, layout.height),
+        BoardArrangement::Stacked => (layout.width, layout.height * 2 + BOARD_GAP),
+    };
 
-    for (idx, row) in layout.rows.iter().enumerate() {
-        let idx: u16 = u16::try_from(idx)?;
-        let y_offset: u16 = (KEY_HEIGHT * idx) + top_padding;
-        let keyboard_rect = Rect::new(left_padding, y_offset, row.width, KEY_HEIGHT);
+    let left_padding: u16 = (terminal_size.width / 2) - (boards_width / 2);
+    let top_padding: u16 = (terminal_size.height / 2) - (boards_height / 2);
+    let board_rect = Rect::new(left_padding, top_padding, layout.width, layout.height);
 
-        draw_row(&row.keys, state, keyboard_rect, frame)
+    // The LED strip shares the line of the device name, which stays centred and clear of it
+    let leds = state.leds.as_ref().map(|leds| led_line(leds, state));
+    let header_margin = leds
+        .as_ref()
+        .map_or(0, |line| line_width(line, board_rect) + 1);
+    if let Some(device) = &state.device {
+        draw_header(
+            &device.to_string(),
+            header_rect(board_rect, header_margin),
+            frame,
+        );
+    }
+    draw_board(
+        &state.key_states,
+        &state.stuck_keys,
+        &state.key_timings,
+        state,
+        board_rect,
+        frame,
+    )?;
+
+    if let Some(line) = leds {
+        draw_leds(line, board_rect, frame);
+    }
+
+    if let Some(board) = &state.compare {
+        let compare_rect = match arrangement {
+            BoardArrangement::Stacked => Rect {
+                y: board_rect.y + layout.height + BOARD_GAP,
+                ..board_rect
+            },
+            _ => Rect {
+                x: board_rect.x + layout.width + BOARD_GAP,
+                ..board_rect
+            },
+        };
+
+        draw_header(
+            &board.device.to_string(),
+            header_rect(compare_rect, 0),
+            frame,
+        );
+        draw_board(
+            &board.key_states,
+            &HashMap::new(),
+            &board.key_timings,
+            state,
+            compare_rect,
+            frame,
+        )?;
+    }
+
+    let bottom: u16 = top_padding + boards_height;
+
+    let extra_keys = state.extra_keys();
+    if !extra_keys.is_empty() {
+        draw_extra_keys(&extra_keys, bottom + 1, frame);
+    }
+
+    if let Some(key_event) = &state.last_key_event {
+        draw_last_key_event(key_event, bottom + 2, frame);
+    }
+
+    if state.polling.samples() > 0 {
+        draw_polling_stats(state, bottom + 4, frame);
+    }
+
+    if !state.stuck_keys.is_empty() {
+        draw_stuck_keys(&state.stuck_keys, bottom + 5, frame);
     }
 
     let less_than_5_pressed = state
@@ -30,30 +151,566 @@ pub fn draw(frame: &mut Frame, state: &App) -> Result<(), KbtError> {
         < 5;
 
     if less_than_5_pressed {
-        draw_help(top_padding + layout.height + 3, frame);
+        draw_help(bottom + 3, frame);
+    }
+
+    if let (Some(batch), Some(serial)) = (&state.batch, &state.serial) {
+        let title = format!(
+            "{} · {} · ctrl+n ends the board early",
+            serial,
+            batch.tally()
+        );
+        draw_header(&title, Rect::new(0, 0, terminal_size.width, 1), frame);
     }
 
     Ok(())
 }
 
-fn draw_row(row_keys: &[KeyUI], state: &App, keyboard_rect: Rect, frame: &mut Frame) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(make_row_constraints(row_keys))
-        .split(keyboard_rect);
+const SERIAL_PROMPT_HELP: &str = "return to start the test, ctrl+r to clear, ctrl+q to quit";
 
-    for (x_pos, ui_key) in row_keys.iter().enumerate() {
-        let key_state = state
-            .key_states
-            .get(&ui_key.key)
-            .unwrap_or(&KeyState::Untouched);
-
-        let borders = match (ui_key.key, ui_key.vertical_key_part) {
-            (Key::Separator, _) => Borders::NONE,
-            (_, Some(VerticalKeyPart::Top)) => Borders::LEFT | Borders::RIGHT | Borders::TOP,
-            (_, Some(VerticalKeyPart::Bottom)) => Borders::LEFT | Borders::RIGHT | Borders::BOTTOM,
-            _ => Borders::ALL,
+// instructions, blank line, input, blank line, last unit, tally, blank line, help
+fn serial_prompt_size(batch: &Batch) -> (u16, u16) {
+    let last_unit_width = batch.last_unit.as_ref().map_or(0, |unit| {
+        unit.serial.chars().count() + unit.problems.join(", ").chars().count() + 10
+    });
+    let width = SERIAL_PROMPT_HELP.chars().count().max(last_unit_width);
+
+    (u16::try_from(width).unwrap_or(u16::MAX), 8)
+}
+
+pub fn serial_prompt_fits(terminal_size: Rect, batch: &Batch) -> bool {
+    let (width, height) = serial_prompt_size(batch);
+    terminal_size.width > width && terminal_size.height > height
+}
+
+pub fn draw_serial_prompt(frame: &mut Frame, batch: &Batch) {
+    let terminal_size: Rect = frame.size();
+    let (width, height) = serial_prompt_size(batch);
+
+    let last_unit = match &batch.last_unit {
+        Some(unit) if unit.problems.is_empty() => Line::styled(
+            format!("{} passed", unit.serial),
+            Style::default().fg(Color::Green),
+        ),
+        Some(unit) => Line::styled(
+            format!("{} failed: {}", unit.serial, unit.problems.join(", ")),
+            Style::default().fg(Color::Red),
+        ),
+        None => Line::default(),
+    };
+    let lines: Vec<Line> = vec![
+        Line::styled(
+            "type or scan the serial of the next board",
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::ITALIC),
+        ),
+        Line::default(),
+        Line::raw(format!(
+            "> {}_",
+            batch.serial_input.as_deref().unwrap_or_default()
+        )),
+        Line::default(),
+        last_unit,
+        Line::raw(batch.tally()),
+        Line::default(),
+        Line::styled(SERIAL_PROMPT_HELP, Style::default().fg(Color::Gray)),
+    ];
+
+    let rect = Rect::new(
+        (terminal_size.width / 2) - (width / 2),
+        (terminal_size.height / 2) - (height / 2),
+        width,
+        height,
+    );
+    frame.render_widget(Paragraph::new(lines).alignment(Alignment::Center), rect);
+}
+
+// Modifier name column, then two cells per alpha key
+const MATRIX_LABEL_WIDTH: u16 = 12;
+
+fn modifier_matrix_size(matrix: &ModifierMatrix) -> (u16, u16) {
+    let alphas = ALPHAS.len() as u16;
+    let modifiers = matrix.modifiers.len() as u16;
+
+    // instructions, blank line, letter header, one row per modifier, blank line, legend, help
+    (MATRIX_LABEL_WIDTH + alphas * 2, modifiers + 6)
+}
+
+pub fn modifier_matrix_fits(terminal_size: Rect, matrix: &ModifierMatrix) -> bool {
+    let (width, height) = modifier_matrix_size(matrix);
+    terminal_size.width > width && terminal_size.height > height
+}
+
+fn draw_modifier_matrix(
+    matrix: &ModifierMatrix,
+    state: &App,
+    frame: &mut Frame,
+) -> Result<(), KbtError> {
+    let terminal_size: Rect = frame.size();
+    let (width, height) = modifier_matrix_size(matrix);
+    let left_padding: u16 = (terminal_size.width / 2) - (width / 2);
+    let top_padding: u16 = (terminal_size.height / 2) - (height / 2);
+
+    let instructions = match matrix.current_modifier() {
+        Some(modifier) => format!("hold {:?}, sweep A to Z, then press Return", modifier),
+        None => String::from("all modifiers done, hold Esc to quit"),
+    };
+
+    let mut lines: Vec<Line> = vec![
+        Line::styled(
+            instructions,
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::ITALIC),
+        ),
+        Line::raw(""),
+        Line::raw(
+            " ".repeat(MATRIX_LABEL_WIDTH as usize)
+                + &ALPHAS
+                    .iter()
+                    .map(|alpha| format!("{:?} ", alpha))
+                    .collect::<String>(),
+        ),
+    ];
+
+    for (step, modifier) in matrix.modifiers.iter().enumerate() {
+        let label_style = if step == matrix.step {
+            Style::default()
+                .fg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
         };
+        let mut spans = vec![Span::styled(
+            format!(
+                "{:<width$}",
+                format!("{:?}", modifier),
+                width = MATRIX_LABEL_WIDTH as usize
+            ),
+            label_style,
+        )];
+        for alpha in ALPHAS {
+            let (cell, style) = match matrix.results.get(&(*modifier, alpha)) {
+                Some(ComboResult::Registered) => ("+", Style::default().fg(Color::LightGreen)),
+                Some(ComboResult::DroppedLetter) => ("x", Style::default().fg(Color::LightRed)),
+                Some(ComboResult::DroppedModifier) => ("m", Style::default().fg(Color::Magenta)),
+                None => (".", Style::default().fg(Color::Gray)),
+            };
+            spans.push(Span::styled(cell, style));
+            spans.push(Span::raw(" "));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    lines.push(Line::raw(""));
+    lines.push(Line::styled(
+        "+ registered  x letter dropped  m modifier dropped",
+        Style::default().fg(Color::Gray),
+    ));
+    lines.push(Line::styled(
+        "hold Esc to quit",
+        Style::default().fg(Color::Gray),
+    ));
+
+    let rect = Rect::new(left_padding, top_padding, width, height);
+    frame.render_widget(Paragraph::new(lines), rect);
+
+    if let Some(key_event) = &state.last_key_event {
+        draw_last_key_event(key_event, top_padding + height + 1, frame);
+    }
+
+    Ok(())
+}
+
+const MATRIX_CELL_WIDTH: u16 = 5;
+const MATRIX_ROW_LABEL_WIDTH: u16 = 4;
+
+fn switch_matrix_dimensions(layout: &KeyboardLayout) -> (u8, u8) {
+    layout
+        .matrix
+        .values()
+        .fold((0, 0), |(rows, cols), (row, col)| {
+            (rows.max(row + 1), cols.max(col + 1))
+        })
+}
+
+fn switch_matrix_title(layout: &KeyboardLayout) -> String {
+    format!(
+        "{} switch matrix, hold keys to look for ghosts",
+        layout.name
+    )
+}
+
+fn switch_matrix_size(layout: &KeyboardLayout) -> (u16, u16) {
+    let (rows, cols) = switch_matrix_dimensions(layout);
+    let title_len = u16::try_from(switch_matrix_title(layout).chars().count()).unwrap_or(u16::MAX);
+
+    // title, blank line, column header, one line per matrix row, blank line, ghost keys
+    (
+        (MATRIX_ROW_LABEL_WIDTH + u16::from(cols) * MATRIX_CELL_WIDTH).max(title_len),
+        u16::from(rows) + 5,
+    )
+}
+
+pub fn switch_matrix_fits(terminal_size: Rect, layout: &KeyboardLayout) -> bool {
+    let (width, height) = switch_matrix_size(layout);
+    terminal_size.width > width && terminal_size.height > height
+}
+
+fn draw_switch_matrix(state: &App, frame: &mut Frame) -> Result<(), KbtError> {
+    let layout = &state.layout;
+    let terminal_size: Rect = frame.size();
+    let (rows, cols) = switch_matrix_dimensions(layout);
+    let (width, height) = switch_matrix_size(layout);
+    let left_padding: u16 = (terminal_size.width / 2) - (width / 2);
+    let top_padding: u16 = (terminal_size.height / 2) - (height / 2);
+
+    let held_keys = state.held_keys();
+    let phantoms = ghosting::phantom_positions(&layout.matrix, &held_keys);
+    let key_at: HashMap<MatrixPosition, Key> = layout
+        .matrix
+        .iter()
+        .map(|(key, position)| (*position, *key))
+        .collect();
+
+    let cell_width = MATRIX_CELL_WIDTH as usize;
+    let mut lines: Vec<Line> = vec![
+        Line::styled(
+            switch_matrix_title(layout),
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::ITALIC),
+        ),
+        Line::raw(""),
+        Line::styled(
+            " ".repeat(MATRIX_ROW_LABEL_WIDTH as usize)
+                + &(0..cols)
+                    .map(|col| format!("{:^cell_width$}", format!("C{}", col)))
+                    .collect::<String>(),
+            Style::default().fg(Color::Gray),
+        ),
+    ];
+
+    for row in 0..rows {
+        let mut spans = vec![Span::styled(
+            format!(
+                "{:<width$}",
+                format!("R{}", row),
+                width = MATRIX_ROW_LABEL_WIDTH as usize
+            ),
+            Style::default().fg(Color::Gray),
+        )];
+        for col in 0..cols {
+            let phantom = phantoms.contains(&(row, col));
+            let (label, style) = match key_at.get(&(row, col)) {
+                Some(key) => {
+                    let label: String = locale::legend(*key, layout.legends, state.locale)
+                        .chars()
+                        .take(3)
+                        .collect();
+                    let style = if state.ghost_keys.contains(key) {
+                        Style::default().fg(Color::White).bg(Color::Red)
+                    } else if held_keys.contains(key) {
+                        Style::default().fg(Color::Black).bg(Color::Yellow)
+                    } else if phantom {
+                        Style::default()
+                            .fg(Color::LightRed)
+                            .add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default()
+                    };
+                    (label, style)
+                }
+                None if phantom => (
+                    String::from("?"),
+                    Style::default()
+                        .fg(Color::LightRed)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                None => (String::from("·"), Style::default().fg(Color::Gray)),
+            };
+            spans.push(Span::styled(format!("{:^cell_width$}", label), style));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    lines.push(Line::raw(""));
+    let mut ghost_keys: Vec<String> = state
+        .ghost_keys
+        .iter()
+        .map(|key| format!("{:?}", key))
+        .collect();
+    ghost_keys.sort();
+    lines.push(if ghost_keys.is_empty() {
+        Line::styled("no ghosts seen yet", Style::default().fg(Color::Gray))
+    } else {
+        Line::styled(
+            format!("ghost keys: {}", ghost_keys.join(" ")),
+            Style::default().fg(Color::LightRed),
+        )
+    });
+
+    let rect = Rect::new(left_padding, top_padding, width, height);
+    frame.render_widget(Paragraph::new(lines), rect);
+
+    if let Some(key_event) = &state.last_key_event {
+        draw_last_key_event(key_event, top_padding + height + 1, frame);
+    }
+
+    Ok(())
+}
+
+const EDITOR_HELP: [&str; 2] = [
+    "arrows move, Return assign by pressing, I insert key, G insert gap, N new row, Delete remove",
+    "+/- resize, [/] width by 0.2u, S save, ctrl+r revert, ctrl+q quit",
+];
+
+// header, blank line, board, blank line, cursor details, status, help
+fn editor_size(layout: &KeyboardLayout) -> (u16, u16) {
+    let help_width = EDITOR_HELP
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    (
+        layout
+            .width
+            .max(u16::try_from(help_width).unwrap_or(u16::MAX)),
+        layout.height + 6 + EDITOR_HELP.len() as u16,
+    )
+}
+
+pub fn editor_fits(terminal_size: Rect, layout: &KeyboardLayout) -> bool {
+    let (width, height) = editor_size(layout);
+    terminal_size.width > width && terminal_size.height > height
+}
+
+pub fn draw_editor(
+    frame: &mut Frame,
+    editor: &LayoutEditor,
+    layout: &KeyboardLayout,
+    locale: Option<Locale>,
+) -> Result<(), KbtError> {
+    let terminal_size: Rect = frame.size();
+    let (width, height) = editor_size(layout);
+    let top_padding: u16 = (terminal_size.height / 2) - (height / 2);
+    let left_padding: u16 = (terminal_size.width / 2) - (layout.width / 2);
+
+    let header = format!(
+        "editing {}{}",
+        editor.path.display(),
+        if editor.is_dirty() { " (unsaved)" } else { "" }
+    );
+    draw_header(
+        &header,
+        Rect::new(0, top_padding, terminal_size.width, 1),
+        frame,
+    );
+
+    let board_top = top_padding + 2;
+    for (row_idx, row) in layout.rows.iter().enumerate() {
+        let y_offset: u16 = (KEY_HEIGHT * u16::try_from(row_idx)?) + board_top;
+        let keyboard_rect = Rect::new(left_padding, y_offset, row.width, KEY_HEIGHT);
+
+        draw_keys(&row.keys, keyboard_rect, frame, |col_idx, ui_key| {
+            let selected = (row_idx, col_idx) == (editor.row, editor.col);
+            let style = match (selected, editor.assigning) {
+                (true, true) => Style::default().fg(Color::Black).bg(Color::Yellow),
+                (true, false) => Style::default()
+                    .fg(Color::LightGreen)
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD),
+                (false, _) => Style::default(),
+            };
+            let border_type = if selected {
+                BorderType::Double
+            } else {
+                BorderType::Plain
+            };
+
+            (
+                border_type,
+                style,
+                locale::legend(ui_key.key, layout.legends, locale),
+            )
+        });
+    }
+
+    let bottom = board_top + layout.height;
+    let details = match editor.current() {
+        Some(current) => {
+            let cells = current.size.static_len() as i16 + current.correction.unwrap_or(0);
+            let key = match current.key {
+                Key::Separator => String::from("gap"),
+                key => format!("{:?}", key),
+            };
+            let matrix = current
+                .matrix
+                .map(|(row, col)| format!(", matrix {},{}", row, col))
+                .unwrap_or_default();
+            format!(
+                "row {} slot {}: {}, {:.1}u{}",
+                editor.row + 1,
+                editor.col + 1,
+                key,
+                f32::from(cells) / 5.0,
+                matrix
+            )
+        }
+        None => String::from("empty row"),
+    };
+    let status = match (&editor.message, editor.assigning) {
+        (_, true) => String::from("press the key for this slot"),
+        (Some(message), false) => message.clone(),
+        (None, false) => String::new(),
+    };
+
+    let lines: Vec<Line> = vec![
+        Line::raw(details),
+        Line::styled(status, Style::default().fg(Color::Yellow)),
+    ]
+    .into_iter()
+    .chain(
+        EDITOR_HELP
+            .iter()
+            .map(|help| Line::styled(*help, Style::default().fg(Color::Gray))),
+    )
+    .collect();
+
+    let lines_rect = Rect::new(
+        (terminal_size.width / 2) - (width / 2),
+        bottom + 1,
+        width,
+        lines.len() as u16,
+    );
+    frame.render_widget(
+        Paragraph::new(lines).alignment(Alignment::Center),
+        lines_rect,
+    );
+
+    Ok(())
+}
+
+const LEARNER_HELP: &str = "ctrl+n to end the row, ctrl+r to start over, ctrl+q to save and quit";
+
+// instructions, blank line, board, blank line, status, help
+fn learner_size(layout: &KeyboardLayout) -> (u16, u16) {
+    let help_width = u16::try_from(LEARNER_HELP.chars().count()).unwrap_or(u16::MAX);
+
+    (layout.width.max(help_width), layout.height + 6)
+}
+
+pub fn learner_fits(terminal_size: Rect, layout: &KeyboardLayout) -> bool {
+    let (width, height) = learner_size(layout);
+    terminal_size.width > width && terminal_size.height > height
+}
+
+pub fn draw_learner(
+    frame: &mut Frame,
+    learner: &LayoutLearner,
+    layout: &KeyboardLayout,
+    locale: Option<Locale>,
+) -> Result<(), KbtError> {
+    let terminal_size: Rect = frame.size();
+    let (width, height) = learner_size(layout);
+    let top_padding: u16 = (terminal_size.height / 2) - (height / 2);
+    let left_padding: u16 = (terminal_size.width / 2) - (layout.width / 2);
+
+    let instructions = format!(
+        "press the keys of row {} from left to right",
+        learner.rows.len() + 1
+    );
+    draw_header(
+        &instructions,
+        Rect::new(0, top_padding, terminal_size.width, 1),
+        frame,
+    );
+
+    let board_top = top_padding + 2;
+    let last_key = learner.row.last();
+    for (row_idx, row) in layout.rows.iter().enumerate() {
+        let y_offset: u16 = (KEY_HEIGHT * u16::try_from(row_idx)?) + board_top;
+        let keyboard_rect = Rect::new(left_padding, y_offset, row.width, KEY_HEIGHT);
+        let current_row = row_idx == learner.rows.len();
+
+        draw_keys(&row.keys, keyboard_rect, frame, |_, ui_key| {
+            let style = if current_row && Some(&ui_key.key) == last_key {
+                Style::default().fg(Color::Yellow)
+            } else if current_row {
+                Style::default()
+            } else {
+                key_style(&KeyState::Released)
+            };
+
+            (
+                BorderType::Plain,
+                style,
+                locale::legend(ui_key.key, layout.legends, locale),
+            )
+        });
+    }
+
+    let lines: Vec<Line> = vec![
+        Line::styled(
+            learner.message.clone().unwrap_or_default(),
+            Style::default().fg(Color::Yellow),
+        ),
+        Line::styled(LEARNER_HELP, Style::default().fg(Color::Gray)),
+    ];
+    let lines_rect = Rect::new(
+        (terminal_size.width / 2) - (width / 2),
+        board_top + layout.height + 1,
+        width,
+        lines.len() as u16,
+    );
+    frame.render_widget(
+        Paragraph::new(lines).alignment(Alignment::Center),
+        lines_rect,
+    );
+
+    Ok(())
+}
+
+fn draw_board(
+    key_states: &HashMap<Key, KeyState>,
+    stuck_keys: &HashMap<Key, StuckKey>,
+    key_timings: &KeyTimings,
+    state: &App,
+    board_rect: Rect,
+    frame: &mut Frame,
+) -> Result<(), KbtError> {
+    for (idx, row) in state.layout.rows.iter().enumerate() {
+        let idx: u16 = u16::try_from(idx)?;
+        let y_offset: u16 = (KEY_HEIGHT * idx) + board_rect.y;
+        let keyboard_rect = Rect::new(board_rect.x, y_offset, row.width, KEY_HEIGHT);
+
+        draw_row(
+            &row.keys,
+            key_states,
+            stuck_keys,
+            key_timings,
+            state,
+            keyboard_rect,
+            frame,
+        )
+    }
+
+    Ok(())
+}
+
+fn draw_row(
+    row_keys: &[KeyUI],
+    key_states: &HashMap<Key, KeyState>,
+    stuck_keys: &HashMap<Key, StuckKey>,
+    key_timings: &KeyTimings,
+    state: &App,
+    keyboard_rect: Rect,
+    frame: &mut Frame,
+) {
+    draw_keys(row_keys, keyboard_rect, frame, |_, ui_key| {
+        let key_state = key_states.get(&ui_key.key).unwrap_or(&KeyState::Untouched);
 
         let border_type = match key_state {
             KeyState::Pressed => BorderType::Double,
@@ -61,20 +718,69 @@ fn draw_row(row_keys: &[KeyUI], state: &App, keyboard_rect: Rect, frame: &mut Fr
             KeyState::Untouched => BorderType::Plain,
         };
 
-        let style = match key_state {
-            KeyState::Pressed => Style::default().fg(Color::Yellow),
-            KeyState::Released => Style::default()
-                .fg(Color::LightGreen)
-                .add_modifier(Modifier::BOLD),
-            KeyState::Untouched => Style::default(),
+        let style = match stuck_keys.get(&ui_key.key) {
+            Some(stuck_key) => stuck_style(stuck_key),
+            None if state.differs(&ui_key.key) => key_style(key_state).fg(Color::LightRed),
+            None if key_timings.chatter.contains_key(&ui_key.key) => {
+                key_style(key_state).fg(Color::LightMagenta)
+            }
+            None => key_style(key_state),
         };
 
+        let label = locale::legend(ui_key.key, state.layout.legends, state.locale);
+
+        (border_type, style, label)
+    })
+}
+
+// Lays out one row of keys, `look` gives the border, style and label of the key at an index
+fn draw_keys(
+    row_keys: &[KeyUI],
+    keyboard_rect: Rect,
+    frame: &mut Frame,
+    look: impl Fn(usize, &KeyUI) -> (BorderType, Style, String),
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(make_row_constraints(row_keys))
+        .split(keyboard_rect);
+
+    let mut chunk_idx: usize = 0;
+    for (x_pos, ui_key) in row_keys.iter().enumerate() {
+        let previous = x_pos.checked_sub(1).and_then(|idx| row_keys.get(idx));
+        let stacked = VerticalKeyPart::is_stacked_below(previous, ui_key);
+        if x_pos > 0 && !stacked {
+            chunk_idx += 1;
+        }
+        let chunk = chunks[chunk_idx];
+
+        let (borders, rect) = match (ui_key.key, ui_key.vertical_key_part) {
+            (Key::Separator, _) => (Borders::NONE, chunk),
+            (_, Some(VerticalKeyPart::Top)) => {
+                (Borders::LEFT | Borders::RIGHT | Borders::TOP, chunk)
+            }
+            (_, Some(VerticalKeyPart::Bottom)) => {
+                (Borders::LEFT | Borders::RIGHT | Borders::BOTTOM, chunk)
+            }
+            (_, Some(VerticalKeyPart::UpperHalf)) => (
+                Borders::LEFT | Borders::RIGHT | Borders::TOP,
+                Rect::new(chunk.x, chunk.y, chunk.width, KEY_HEIGHT - 1),
+            ),
+            (_, Some(VerticalKeyPart::LowerHalf)) => (
+                Borders::LEFT | Borders::RIGHT,
+                Rect::new(chunk.x, chunk.y + KEY_HEIGHT - 1, chunk.width, 1),
+            ),
+            _ => (Borders::ALL, chunk),
+        };
+
+        let (border_type, style, label) = look(x_pos, ui_key);
+
         let block = Block::default().borders(borders).border_type(border_type);
 
         let label = if let Some(VerticalKeyPart::Bottom) = ui_key.vertical_key_part {
             String::new()
         } else {
-            ui_key.key.to_string()
+            label
         };
 
         let text = Paragraph::new(label)
@@ -82,13 +788,34 @@ fn draw_row(row_keys: &[KeyUI], state: &App, keyboard_rect: Rect, frame: &mut Fr
             .style(style)
             .alignment(Alignment::Center);
 
-        frame.render_widget(text, chunks[x_pos])
+        frame.render_widget(text, rect)
+    }
+}
+
+fn key_style(key_state: &KeyState) -> Style {
+    match key_state {
+        KeyState::Pressed => Style::default().fg(Color::Yellow),
+        KeyState::Released => Style::default()
+            .fg(Color::LightGreen)
+            .add_modifier(Modifier::BOLD),
+        KeyState::Untouched => Style::default(),
+    }
+}
+
+fn stuck_style(stuck_key: &StuckKey) -> Style {
+    match stuck_key {
+        StuckKey::Held => Style::default().fg(Color::White).bg(Color::Red),
+        StuckKey::ReleaseLost => Style::default().fg(Color::White).bg(Color::Magenta),
     }
 }
 
 fn make_row_constraints(keys: &[KeyUI]) -> Vec<Constraint> {
     keys.iter()
-        .map(|key| {
+        .enumerate()
+        .filter(|(idx, key)| {
+            !VerticalKeyPart::is_stacked_below(idx.checked_sub(1).and_then(|i| keys.get(i)), key)
+        })
+        .map(|(_, key)| {
             Constraint::Length(
                 u16::try_from(key.size.static_len() as i16 + key.size_correction.unwrap_or(0))
                     .unwrap_or(0),
@@ -117,6 +844,222 @@ fn draw_help(y_offset: u16, frame: &mut Frame) {
     }
 }
 
+// Imports leave keys they can't bind blank, that shouldn't pass for a gap of the board
+fn draw_unbound_keys(layout: &KeyboardLayout, frame: &mut Frame) {
+    let terminal_size = frame.size();
+    if layout.unbound_keys == 0 || terminal_size.height == 0 {
+        return;
+    }
+
+    let message = format!(
+        "{} keys of this layout have no kbt key and are drawn as gaps",
+        layout.unbound_keys
+    );
+    let message_len = u16::try_from(message.chars().count())
+        .unwrap_or(terminal_size.width)
+        .min(terminal_size.width);
+    let rect = Rect::new(
+        (terminal_size.width / 2) - (message_len / 2),
+        terminal_size.height - 1,
+        message_len,
+        1,
+    );
+
+    frame.render_widget(
+        Paragraph::new(message).style(Style::default().fg(Color::Yellow)),
+        rect,
+    );
+}
+
+// Line above a board, `margin` cells are left free at both ends
+fn header_rect(board_rect: Rect, margin: u16) -> Rect {
+    let margin = margin.min(board_rect.width / 2);
+    Rect {
+        x: board_rect.x + margin,
+        y: board_rect.y.saturating_sub(2),
+        width: board_rect.width - 2 * margin,
+        height: 1,
+    }
+}
+
+fn draw_header(message: &str, area: Rect, frame: &mut Frame) {
+    let message_len = u16::try_from(message.chars().count())
+        .unwrap_or(area.width)
+        .min(area.width);
+
+    let x_offset: u16 = area.x + (area.width / 2) - (message_len / 2);
+
+    let rect = Rect::new(x_offset, area.y, message_len, 1);
+
+    let header = Paragraph::new(message).style(
+        Style::default()
+            .fg(Color::Green)
+            .add_modifier(Modifier::ITALIC),
+    );
+
+    frame.render_widget(header, rect);
+}
+
+// Right-aligned on the header line of the board, a key label turns green once its LED was seen flipping
+fn led_line(leds: &LockLeds, state: &App) -> Line<'static> {
+    let mut spans: Vec<Span> = Vec::new();
+    for lock_key in LockKey::ALL {
+        let label_style = match state.led_test.results.get(&lock_key) {
+            Some(LedCheck::Flipped) => Style::default().fg(Color::LightGreen),
+            Some(LedCheck::DidNotFlip) => Style::default().fg(Color::LightRed),
+            None => Style::default(),
+        };
+        let (led, led_style) = if leds.is_on(lock_key) {
+            ("●", Style::default().fg(Color::Yellow))
+        } else {
+            ("○", Style::default().fg(Color::Gray))
+        };
+
+        if !spans.is_empty() {
+            spans.push(Span::raw(" "));
+        }
+        spans.push(Span::styled(led, led_style));
+        spans.push(Span::styled(lock_key.label(), label_style));
+    }
+
+    Line::from(spans)
+}
+
+fn line_width(line: &Line, board_rect: Rect) -> u16 {
+    u16::try_from(line.width())
+        .unwrap_or(board_rect.width)
+        .min(board_rect.width)
+}
+
+fn draw_leds(line: Line, board_rect: Rect, frame: &mut Frame) {
+    let line_len = line_width(&line, board_rect);
+
+    let rect = Rect::new(
+        board_rect.x + board_rect.width - line_len,
+        board_rect.y.saturating_sub(2),
+        line_len,
+        1,
+    );
+
+    frame.render_widget(Paragraph::new(line), rect);
+}
+
+fn draw_extra_keys(extra_keys: &[(String, &KeyState)], y_offset: u16, frame: &mut Frame) {
+    let terminal_size = frame.size();
+
+    if terminal_size.height > y_offset {
+        let mut spans = vec![Span::styled(
+            "extra keys:",
+            Style::default().fg(Color::Gray),
+        )];
+        for (name, key_state) in extra_keys {
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(format!("[{}]", name), key_style(key_state)));
+        }
+
+        let line = Line::from(spans);
+        let line_len = u16::try_from(line.width())
+            .unwrap_or(terminal_size.width)
+            .min(terminal_size.width);
+
+        let x_offset: u16 = (terminal_size.width / 2) - (line_len / 2);
+
+        let rect = Rect::new(x_offset, y_offset, line_len, 1);
+
+        frame.render_widget(Paragraph::new(line), rect);
+    }
+}
+
+fn draw_stuck_keys(stuck_keys: &HashMap<Key, StuckKey>, y_offset: u16, frame: &mut Frame) {
+    let terminal_size = frame.size();
+
+    if terminal_size.height > y_offset {
+        let mut stuck_keys: Vec<(String, &StuckKey)> = stuck_keys
+            .iter()
+            .map(|(key, stuck_key)| (format!("{:?}", key), stuck_key))
+            .collect();
+        stuck_keys.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut spans = vec![Span::styled(
+            "stuck keys:",
+            Style::default().fg(Color::Gray),
+        )];
+        for (name, stuck_key) in stuck_keys {
+            let kind = match stuck_key {
+                StuckKey::Held => "held",
+                StuckKey::ReleaseLost => "release lost",
+            };
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(
+                format!("[{} {}]", name, kind),
+                stuck_style(stuck_key),
+            ));
+        }
+
+        let line = Line::from(spans);
+        let line_len = u16::try_from(line.width())
+            .unwrap_or(terminal_size.width)
+            .min(terminal_size.width);
+
+        let x_offset: u16 = (terminal_size.width / 2) - (line_len / 2);
+
+        let rect = Rect::new(x_offset, y_offset, line_len, 1);
+
+        frame.render_widget(Paragraph::new(line), rect);
+    }
+}
+
+fn draw_last_key_event(key_event: &KeyEventType, y_offset: u16, frame: &mut Frame) {
+    let terminal_size = frame.size();
+
+    if terminal_size.height > y_offset {
+        let message = key_event.to_string();
+        let message_len = u16::try_from(message.chars().count())
+            .unwrap_or(terminal_size.width)
+            .min(terminal_size.width);
+
+        let x_offset: u16 = (terminal_size.width / 2) - (message_len / 2);
+
+        let rect = Rect::new(x_offset, y_offset, message_len, 1);
+
+        let detail = Paragraph::new(message).style(Style::default().fg(Color::Gray));
+
+        frame.render_widget(detail, rect);
+    }
+}
+
+fn draw_polling_stats(state: &App, y_offset: u16, frame: &mut Frame) {
+    let terminal_size = frame.size();
+
+    if terminal_size.height > y_offset {
+        let message = match state.polling.estimate() {
+            Some(estimate) => format!(
+                "polling ~{} Hz, interval {:.3} ms, jitter {:.3} ms ({} samples)",
+                estimate.rate_hz, estimate.interval_ms, estimate.jitter_ms, estimate.samples
+            ),
+            None if state.polling.has_enough_samples() => format!(
+                "polling rate unclear, keep rolling across keys ({} samples)",
+                state.polling.samples()
+            ),
+            None => format!(
+                "roll across keys to estimate the polling rate ({} samples)",
+                state.polling.samples()
+            ),
+        };
+        let message_len = u16::try_from(message.chars().count())
+            .unwrap_or(terminal_size.width)
+            .min(terminal_size.width);
+
+        let x_offset: u16 = (terminal_size.width / 2) - (message_len / 2);
+
+        let rect = Rect::new(x_offset, y_offset, message_len, 1);
+
+        let stats = Paragraph::new(message).style(Style::default().fg(Color::Cyan));
+
+        frame.render_widget(stats, rect);
+    }
+}
+
 pub fn draw_too_small(frame: &mut Frame) {
     let terminal_size = frame.size();
 